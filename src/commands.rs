@@ -3,6 +3,9 @@
 
 pub mod bump;
 pub mod clone;
+pub mod du;
+pub mod edit;
+pub mod fetch;
 pub mod generate;
 pub mod get;
 pub mod init;
@@ -11,9 +14,12 @@ pub mod link;
 pub mod list;
 pub mod metadata;
 pub mod package_path;
+pub mod prune;
 pub mod publish;
 pub mod sync;
 pub mod unlink;
+pub mod update;
+pub mod verify;
 
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -24,6 +30,7 @@ use tracing::Level;
 use typst_syntax::package::{PackageVersion, VersionBound};
 
 use crate::build;
+use crate::utils::git::GitBackend;
 use crate::utils::output::OutputFormat;
 
 fn parse_eco<T>(s: &str) -> Result<T, String>
@@ -100,12 +107,12 @@ pub struct InitArgs {
     #[arg(short = 'p', long)]
     populate: bool,
 
-    /// Categories to add to your typst.toml.
+    /// Categories to add to your typst.toml, among those of Typst Universe.
     #[arg(short = 'C', long)]
     #[clap(value_delimiter = ',')]
     categories: Option<Vec<String>>,
 
-    /// Disciplines to add to your typst.toml.
+    /// Disciplines to add to your typst.toml, among those of Typst Universe.
     #[arg(long)]
     #[clap(value_delimiter = ',')]
     disciplines: Option<Vec<String>>,
@@ -135,6 +142,22 @@ pub struct LinkArgs {
     #[arg(short, long)]
     pub no_copy: bool,
 
+    /// Link the package under this version instead of the one in `typst.toml`.
+    #[arg(long, value_parser = parse_eco::<PackageVersion>)]
+    pub as_version: Option<PackageVersion>,
+
+    /// Mark the link as a development copy with this name, e.g. `dev`.
+    ///
+    /// Typst only imports numeric versions, so unless `--as-version` is given,
+    /// the package is linked as the first free 0.0.x, or the version the alias
+    /// has been linked as before.
+    #[arg(long)]
+    pub alias: Option<String>,
+
+    /// Keep running and copy changed files again into the linked package.
+    #[arg(short, long, conflicts_with = "no_copy")]
+    pub watch: bool,
+
     /// Namespace
     pub namespace: Option<String>,
 
@@ -160,35 +183,87 @@ pub struct LinkArgs {
 }
 
 /// Arguments for the `list` and `tree` commands.
-/// These commands display the packages in the local storage.
+/// These commands display the packages in the local storage and the download cache.
 #[derive(Parser, Clone, Debug, PartialEq)]
 pub struct ListTreeArgs {
-    /// List all packages, including those in the `@preview` namespace.
-    #[arg(short, long)]
-    pub all: bool,
+    /// Packages to list, e.g. `@preview`, `@local/mypkg` or `@preview/cet*`.
+    /// Everything is listed if omitted.
+    #[arg(value_parser = clap::value_parser!(list::Query))]
+    pub queries: Vec<list::Query>,
 
-    /// Specify subdirectories to include in the list.
-    #[arg(short, long, num_args = 1..)]
-    pub include: Option<Vec<String>>,
+    /// How to sort the packages.
+    #[arg(short, long, value_enum, default_value_t)]
+    pub sort: list::SortKey,
+
+    /// Only show the highest version of each package.
+    #[arg(long)]
+    pub latest_only: bool,
+
+    /// Show at most this many versions.
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
 
     /// Display the packages as a tree. Only works with text output.
     #[arg(short, long)]
     pub tree: bool,
+
+    /// Read the manifest of each version and show its details.
+    #[arg(short, long)]
+    pub long: bool,
+
+    /// Deprecated: both stores, including `@preview`, are always listed.
+    #[arg(short, long, hide = true)]
+    pub all: bool,
 }
 
 /// Arguments for the `bump` command.
 /// This command bump the version of your package
 #[derive(Parser, Clone, Debug, PartialEq)]
 pub struct BumpArgs {
-    /// The tag to look at when you bump other files.
-    /// If the file is written in markdown or html, it will looks into the code to find `<tag>0.1.0</tag>`
+    /// The tag to look at when you bump other files, e.g. `<tag>0.1.0</tag>`.
+    /// Imports of the package are always bumped in Markdown and Typst files.
+    /// Default to `tag` in `[tool.utpm.bump]`.
     #[arg(short, long)]
     pub tag: Option<String>,
 
-    /// Files to include in the list. (typst.toml is already included)
+    /// Files to include in the list, besides `files` in `[tool.utpm.bump]`.
+    /// (typst.toml and the examples importing the package are already included)
+    /// Paths are relative to the project directory.
     #[arg(short, long, num_args = 1..)]
     pub include: Vec<String>,
 
+    /// Allow a version lower than the current one.
+    #[arg(long)]
+    pub allow_downgrade: bool,
+
+    /// Add a section for the new version to a changelog, `CHANGELOG.md` by default.
+    /// Default to `changelog` in `[tool.utpm]`.
+    #[arg(long, num_args = 0..=1, default_missing_value = "CHANGELOG.md")]
+    pub changelog: Option<String>,
+
+    /// Commit the bumped files.
+    #[arg(long)]
+    pub commit: bool,
+
+    /// Tag the commit made with `--commit`.
+    #[arg(long, requires = "commit")]
+    pub tag_git: bool,
+
+    /// The commit message, where `{version}` stands for the new version.
+    /// Default to `message` in `[tool.utpm.bump]`, or `v{version}`.
+    #[arg(short, long)]
+    pub message: Option<String>,
+
+    /// The name of the tag, where `{version}` stands for the new version.
+    /// Default to `git_tag` in `[tool.utpm.bump]`, or `v{version}`.
+    #[arg(long)]
+    pub tag_format: Option<String>,
+
+    /// Commit even if the working tree has uncommitted changes.
+    #[arg(long)]
+    pub allow_dirty: bool,
+
+    /// `major`, `minor`, `patch`, or an explicit version such as `1.2.3`.
     pub new_version: String,
 }
 
@@ -252,8 +327,9 @@ pub struct GenerateArgs {
 pub struct CloneArgs {
     /// The package to clone.
     ///
-    /// Format: @namespace/package:version
-    /// Example: @preview/example:1.0.0
+    /// Format: @namespace/package:version, the namespace defaults to preview.
+    /// Without a full version, the latest matching one is cloned.
+    /// Example: @preview/example:1.0.0, example:1 or example
     #[arg()]
     pub package: String,
 
@@ -295,6 +371,70 @@ pub struct UnlinkArgs {
     yes: bool,
 }
 
+/// Arguments for the `prune` command.
+/// This command removes the downloaded packages that are not imported anymore.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct PruneArgs {
+    /// Project directories, Typst files or lockfiles whose imports are kept.
+    /// Default to the current directory.
+    pub paths: Vec<PathBuf>,
+
+    /// Also keep the N latest versions of each package.
+    #[arg(short, long, default_value_t = 0)]
+    pub keep_latest: usize,
+
+    /// Only remove versions older than this, e.g. `30d`, `12h` or `2w`.
+    #[arg(long, value_parser = prune::parse_age)]
+    pub older_than: Option<std::time::Duration>,
+
+    /// Confirm the deletion without a prompt.
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+/// Arguments for the `du` command.
+/// This command reports the disk usage of the package stores.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct DuArgs {
+    /// How many levels to detail below each store: 1 for namespaces, 2 for packages,
+    /// 3 for versions. Default to everything.
+    #[arg(short, long)]
+    pub depth: Option<usize>,
+}
+
+/// Arguments for the `verify` command.
+/// This command checks the integrity of the packages in the stores.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct VerifyArgs {
+    /// Packages to verify, e.g. `@preview`, `@local/mypkg` or `@preview/cet*`.
+    /// Everything is verified if omitted.
+    #[arg(value_parser = clap::value_parser!(list::Query))]
+    pub queries: Vec<list::Query>,
+
+    /// Compare downloaded packages to a fresh download from the registry
+    /// instead of the recorded checksums.
+    #[arg(short, long)]
+    pub remote: bool,
+
+    /// Download the corrupted packages again without a prompt.
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+/// Arguments for the `fetch` command.
+/// This command downloads every package a project imports, for offline builds.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct FetchArgs {
+    /// Project directories or Typst files whose imports are fetched.
+    /// Default to the current directory.
+    pub paths: Vec<PathBuf>,
+
+    /// Also copy every dependency into this directory, laid out like a package
+    /// directory that `TYPST_PACKAGE_PATH` can point to.
+    #[arg(long)]
+    pub vendor: Option<PathBuf>,
+}
+
 /// Arguments for the `install` command.
 /// This command installs a package from a git repository, not from Typst Universe.
 /// You will require to have git install on your machine.
@@ -307,6 +447,20 @@ pub struct InstallArgs {
     /// The namespace you want to put your installed package. Default to local
     #[arg(short, long)]
     pub namespace: Option<String>,
+
+    /// Branch, tag or commit to check out after cloning.
+    #[arg(short, long = "ref")]
+    pub reference: Option<String>,
+}
+
+/// Arguments for the `update` command.
+/// This command fetches the sources of packages installed with `install` again
+/// and links any new version next to the old ones.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct UpdateArgs {
+    /// Package to update, e.g. `mypkg` or `@local/mypkg`. Defaults to every
+    /// installed package.
+    pub name: Option<String>,
 }
 
 #[derive(Parser, Clone, Debug, PartialEq)]
@@ -328,7 +482,8 @@ pub struct SyncArgs {
 /// This command gets package information from Typst Universe.
 /// By default: Lists all available packages.
 pub struct GetArgs {
-    /// Package names to query (e.g., @preview/example).
+    /// Packages to query, e.g. `example`, `@preview/example`, `example:1` or
+    /// `@preview/example:1.0.0`. The latest matching version is shown.
     /// Leave empty to list all packages.
     pub packages: Vec<String>,
 }
@@ -343,8 +498,49 @@ pub struct MetadataArgs {
 
     /// Specific field to extract (e.g., name, version, authors).
     /// If not specified, all metadata will be displayed.
+    ///
+    /// Accepts dotted paths into typst.toml, like `template.entrypoint`,
+    /// `tool.utpm.exclude` or `package.authors[0]`.
     #[arg(short, long)]
     pub field: Option<String>,
+
+    /// Template to print, where `{field}` is replaced by the value of that field.
+    ///
+    /// Example: utpm prj metadata --format '{name}@{version}'
+    #[arg(short = 'F', long, conflicts_with = "field")]
+    pub format: Option<String>,
+}
+
+/// Arguments for the `set` command.
+/// This command sets a value of typst.toml.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct SetArgs {
+    /// The dotted key to set, e.g. `description` or `template.entrypoint`.
+    /// Keys outside `template` and `tool` are looked up in `[package]`.
+    #[arg()]
+    pub key: String,
+
+    /// The new value.
+    #[arg()]
+    pub value: String,
+
+    /// Parse the value as TOML (e.g. `["a", "b"]`) instead of using it as a string.
+    #[arg(short, long)]
+    pub toml: bool,
+}
+
+/// Arguments for the `add` and `remove` commands.
+/// These commands edit an array of typst.toml.
+#[derive(Parser, Clone, Debug, PartialEq)]
+pub struct EditListArgs {
+    /// The dotted key of the array, e.g. `keywords` or `tool.utpm.exclude`.
+    /// Keys outside `template` and `tool` are looked up in `[package]`.
+    #[arg()]
+    pub key: String,
+
+    /// The values to add or remove.
+    #[arg(required = true, num_args = 1..)]
+    pub values: Vec<String>,
 }
 
 /// An enumeration of subcommands for managing local packages.
@@ -369,6 +565,20 @@ pub enum PackagesArgs {
     /// Install a package from a git repository into a namespace.
     #[command(visible_alias = "i")]
     Install(InstallArgs),
+
+    /// Update packages installed from a git repository or a local path.
+    #[command(visible_alias = "up")]
+    Update(UpdateArgs),
+
+    /// Remove downloaded packages that your projects don't import anymore.
+    #[command(visible_alias = "gc")]
+    Prune(PruneArgs),
+
+    /// Show the disk usage of local packages, the download cache and UTPM's data.
+    Du(DuArgs),
+
+    /// Check that the installed packages are complete and unmodified.
+    Verify(VerifyArgs),
 }
 
 /// An enumeration of subcommands for managing the project project.
@@ -397,6 +607,10 @@ pub enum ProjectArgs {
     #[command(visible_alias = "b")]
     Bump(BumpArgs),
 
+    /// Download every package the project imports, for offline builds.
+    #[command(visible_alias = "f")]
+    Fetch(FetchArgs),
+
     /// Synchronise all your dependencies into their last version.
     #[command()]
     #[command(visible_alias = "s")]
@@ -406,6 +620,16 @@ pub enum ProjectArgs {
     #[command()]
     #[command(visible_alias = "m")]
     Metadata(MetadataArgs),
+
+    /// Set a value of typst.toml.
+    Set(SetArgs),
+
+    /// Add values to an array of typst.toml, such as keywords or authors.
+    Add(EditListArgs),
+
+    /// Remove values from an array of typst.toml.
+    #[command(visible_alias = "rm")]
+    Remove(EditListArgs),
 }
 
 /// The main command-line interface for UTPM.
@@ -462,4 +686,17 @@ pub struct Cli {
     /// Example: utpm --dry-run prj link
     #[arg(default_value_t = false, short = 'D', long, global = true)]
     pub dry_run: bool,
+
+    /// The implementation used for git operations.
+    ///
+    /// Backends: cli (default), lib (requires the `git_lib` feature)
+    /// Example: utpm --git-backend lib pkg install <url>
+    #[arg(
+        default_value = "cli",
+        long,
+        global = true,
+        env = "UTPM_GIT_BACKEND",
+        value_enum
+    )]
+    pub git_backend: GitBackend,
 }
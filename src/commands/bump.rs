@@ -1,77 +1,475 @@
-use std::borrow::Cow;
-use std::fs::{File, read_to_string, write};
-use std::io::Write;
+use std::fs::read_to_string;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::SystemTime;
 
+use fmt_derive::{Debug, Display};
 use itertools::Itertools;
+use regex::{Captures, Regex};
+use serde::Serialize;
 use toml_edit::{DocumentMut, value};
 use tracing::instrument;
-use typst_syntax::package::PackageVersion;
+use typst_syntax::package::{PackageManifest, PackageVersion};
 
 use crate::{
-    utils::{self, dryrun::get_dry_run, state::Result},
-    utpm_log,
+    utils::{
+        self,
+        dryrun::get_dry_run,
+        format_date,
+        git::{self, GitOps},
+        imports::{read_imports, typst_files},
+        specs::{BumpConfig, Extra},
+        state::{Result, UtpmError},
+        store::write_files,
+    },
+    utpm_bail, utpm_log,
 };
 
 use super::BumpArgs;
 
-/// Wraps a value in HTML-like tags if a tag is provided.
+/// The version to bump to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bump {
+    /// Increments the major version and resets the others, e.g. 1.2.3 to 2.0.0.
+    Major,
+    /// Increments the minor version and resets the patch, e.g. 1.2.3 to 1.3.0.
+    Minor,
+    /// Increments the patch version, e.g. 1.2.3 to 1.2.4.
+    Patch,
+    /// An explicit version.
+    To(PackageVersion),
+}
+
+impl FromStr for Bump {
+    type Err = UtpmError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "major" => Ok(Self::Major),
+            "minor" => Ok(Self::Minor),
+            "patch" => Ok(Self::Patch),
+            _ => PackageVersion::from_str(s)
+                .map(Self::To)
+                .map_err(|err| UtpmError::InvalidVersion(s.into(), err.into())),
+        }
+    }
+}
+
+impl Bump {
+    /// Computes the version following `current`.
+    pub fn apply(self, current: PackageVersion) -> PackageVersion {
+        let PackageVersion {
+            major,
+            minor,
+            patch,
+        } = current;
+        match self {
+            Self::Major => PackageVersion {
+                major: major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Self::Minor => PackageVersion {
+                major,
+                minor: minor + 1,
+                patch: 0,
+            },
+            Self::Patch => PackageVersion {
+                major,
+                minor,
+                patch: patch + 1,
+            },
+            Self::To(version) => version,
+        }
+    }
+}
+
+/// What `prj bump` changed, or would change in dry-run mode.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("Bumped from {old_version} to {new_version} ({}){}{}", files.join(", "), commit.as_ref().map(|message| format!(", committed as \"{message}\"")).unwrap_or_default(), git_tag.as_ref().map(|tag| format!(", tagged {tag}")).unwrap_or_default())]
+pub struct Bumped {
+    pub old_version: String,
+    pub new_version: String,
+    pub files: Vec<String>,
+    /// The message of the commit made with `--commit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The tag made with `--tag-git`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_tag: Option<String>,
+}
+
+/// The default format of commit messages and tags, e.g. `v1.2.0`.
+const DEFAULT_GIT_FORMAT: &str = "v{version}";
+
+/// A planned change of a line in a bumped file.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{file}:{line}\n- {before}\n+ {after}")]
+pub struct Change {
+    pub file: String,
+    /// The line number, starting at 1.
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Replaces the current version with the new one where it refers to this package.
+pub struct Replacer {
+    old_version: String,
+    new_version: String,
+    /// `@namespace/<name>:<old>` imports, only used in Markdown and Typst files.
+    imports: Regex,
+    /// The regexes of `--tag` and of `[tool.utpm.bump] patterns`.
+    rules: Vec<Regex>,
+    /// Any occurrence of the version, for other files without rules.
+    plain: Regex,
+}
+
+impl Replacer {
+    /// Prepares the replacement of `old_version` in files of the package `name`.
+    ///
+    /// Patterns are regexes containing `{version}`, which stands for the current
+    /// version.
+    pub fn new(
+        name: &str,
+        old_version: &str,
+        new_version: &str,
+        tag: Option<&str>,
+        patterns: &[String],
+    ) -> Result<Self> {
+        let version = regex::escape(old_version);
+        let mut rules = vec![];
+        if let Some(tag) = tag {
+            let tag = regex::escape(tag);
+            rules.push(Regex::new(&format!("<{tag}>{version}</{tag}>")).unwrap());
+        }
+        for pattern in patterns {
+            if !pattern.contains("{version}") {
+                utpm_bail!(
+                    BumpPattern,
+                    pattern.clone(),
+                    "it must contain {version}".into()
+                );
+            }
+            let regex = Regex::new(&pattern.replace("{version}", &version))
+                .map_err(|err| UtpmError::BumpPattern(pattern.clone(), err.to_string()))?;
+            rules.push(regex);
+        }
+        Ok(Self {
+            old_version: old_version.into(),
+            new_version: new_version.into(),
+            imports: Regex::new(&format!(
+                r"@[A-Za-z0-9_-]+/{}:{version}\b",
+                regex::escape(name)
+            ))
+            .unwrap(),
+            rules,
+            plain: Regex::new(&format!(r"\b{version}\b")).unwrap(),
+        })
+    }
+
+    /// Replaces the version in `content`, read from `path`.
+    ///
+    /// Markdown and Typst files only get their imports of the package rewritten,
+    /// besides the tag and the patterns. Other files get every occurrence of the
+    /// version rewritten when there is neither tag nor pattern.
+    pub fn replace(&self, path: &Path, content: &str) -> String {
+        let documented = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("md" | "markdown" | "typ")
+        );
+        let mut rules: Vec<&Regex> = self.rules.iter().collect();
+        if documented {
+            rules.push(&self.imports);
+        } else if rules.is_empty() {
+            rules.push(&self.plain);
+        }
+
+        let mut content = content.to_string();
+        for rule in rules {
+            content = rule
+                .replace_all(&content, |caps: &Captures| {
+                    caps[0].replace(&self.old_version, &self.new_version)
+                })
+                .into_owned();
+        }
+        content
+    }
+}
+
+/// Lists the lines changed between `before` and `after`.
 ///
-/// # Arguments
-/// * `tag` - Optional tag name (e.g., "version")
-/// * `value` - The value to wrap
+/// Replacing a version never adds or removes lines, so lines are compared one by one.
+pub fn diff(file: &str, before: &str, after: &str) -> Vec<Change> {
+    before
+        .lines()
+        .zip(after.lines())
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(i, (before, after))| Change {
+            file: file.into(),
+            line: i + 1,
+            before: before.into(),
+            after: after.into(),
+        })
+        .collect()
+}
+
+/// The header of a new changelog, following <https://keepachangelog.com>.
+const CHANGELOG_HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+";
+
+/// Builds the changelog section of a release, listing the given commit subjects.
+pub fn changelog_entry(version: &str, date: &str, subjects: &[String]) -> String {
+    let mut entry = format!("## [{version}] - {date}\n");
+    if !subjects.is_empty() {
+        entry.push_str("\n### Changed\n\n");
+        for subject in subjects {
+            entry.push_str(&format!("- {subject}\n"));
+        }
+    }
+    entry
+}
+
+/// Adds `entry` above the latest release of a changelog, below `[Unreleased]`.
+///
+/// A new changelog is started if there is none.
+pub fn prepend_changelog(changelog: Option<&str>, entry: &str) -> String {
+    let changelog = changelog.unwrap_or(CHANGELOG_HEADER);
+    let mut offset = 0;
+    for line in changelog.split_inclusive('\n') {
+        if line.starts_with("## ") && !line.starts_with("## [Unreleased]") {
+            return format!("{}{entry}\n{}", &changelog[..offset], &changelog[offset..]);
+        }
+        offset += line.len();
+    }
+    let separator = if changelog.ends_with("\n\n") {
+        ""
+    } else if changelog.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    format!("{changelog}{separator}{entry}")
+}
+
+/// Returns the changelog at `path` with a section added for `version`.
 ///
-/// # Returns
-/// If tag is Some, returns `<tag>value</tag>`. Otherwise, returns the value unchanged.
-fn tag_change<'a>(tag: Option<&str>, value: &'a str) -> Cow<'a, str> {
-    if let Some(tag) = tag {
-        Cow::Owned(format!("<{tag}>{value}</{tag}>"))
+/// The section lists the commits made since the last tag. In dry-run mode, the
+/// section is shown as well.
+fn changelog_content(git: &dyn GitOps, path: &Path, version: &str) -> Result<String> {
+    let subjects = match git
+        .last_tag()
+        .and_then(|tag| git.log_subjects(tag.as_deref()))
+    {
+        Ok(subjects) => subjects,
+        Err(err) => {
+            utpm_log!(warn, "Can't list the commits for the changelog: {}", err);
+            vec![]
+        },
+    };
+    let entry = changelog_entry(version, &format_date(SystemTime::now()), &subjects);
+    if get_dry_run() {
+        utpm_log!(info, "Would add to {}:\n{}", path.display(), entry);
+    }
+    let changelog = if path.exists() {
+        Some(read_to_string(path)?)
     } else {
-        Cow::Borrowed(value)
+        None
+    };
+    Ok(prepend_changelog(changelog.as_deref(), &entry))
+}
+
+/// Directories, besides the template, where examples of the package are looked for.
+const EXAMPLE_DIRS: [&str; 2] = ["examples", "example"];
+
+/// Finds the example and template files of the package importing its current version.
+///
+/// Paths are returned relative to `root`, the directory of the manifest.
+pub fn self_importing_files(root: &Path, manifest: &PackageManifest) -> Result<Vec<String>> {
+    let template = manifest
+        .template
+        .as_ref()
+        .map(|template| template.path.as_str());
+    let mut files = vec![];
+    for dir in template.into_iter().chain(EXAMPLE_DIRS) {
+        let dir = root.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for file in typst_files(&dir)? {
+            let imports_itself = read_imports(&file)?.iter().any(|spec| {
+                spec.name == manifest.package.name && spec.version == manifest.package.version
+            });
+            if imports_itself && let Ok(relative) = file.strip_prefix(root) {
+                files.push(relative.to_string_lossy().to_string());
+            }
+        }
     }
+    files.sort();
+    Ok(files)
 }
 
 /// Bumps the version number in typst.toml and optionally in other files.
 ///
-/// Updates the package version in the manifest and searches for the old version
-/// in specified files, replacing it with the new version. Supports wrapping
-/// versions in HTML-like tags for special file formats.
+/// The new version is either given explicitly or computed from the current one
+/// with `major`, `minor` or `patch`. Lowering the version is refused unless
+/// `--allow-downgrade` is used. Updates the package version in the manifest and in
+/// the included files, the files listed in `[tool.utpm.bump]` and the examples and
+/// templates importing the package. Only the versions referring to this package
+/// are replaced (see [`Replacer`]). With `--changelog`, a section listing the
+/// commits since the last tag is added to the changelog. With `--commit` and
+/// `--tag-git`, the bumped files are committed and tagged, provided the working
+/// tree was clean. In dry-run mode, every planned change is shown.
 #[instrument(skip(cmd))]
 pub async fn run(cmd: &BumpArgs) -> Result<bool> {
     utpm_log!(trace, "executing bump command");
-    let manifest_path = utils::try_find_path(&utils::paths::get_current_dir()?)?;
-    let mut config = read_to_string(&manifest_path)?.parse::<DocumentMut>()?;
+    let current_dir = utils::paths::get_current_dir()?;
+    let manifest_path = utils::try_find_path(&current_dir)?;
+    let manifest = read_to_string(&manifest_path)?;
+    let mut config = manifest.parse::<DocumentMut>()?;
+
+    let bump = Bump::from_str(&cmd.new_version)?;
+    let Some(old_version) = config["package"]["version"].as_str() else {
+        utpm_bail!(
+            InvalidVersion,
+            String::new(),
+            "typst.toml has no package version".into()
+        );
+    };
+    let current = PackageVersion::from_str(old_version)
+        .map_err(|err| UtpmError::InvalidVersion(old_version.into(), err.into()))?;
+    let next = bump.apply(current);
+    if next < current && !cmd.allow_downgrade {
+        utpm_bail!(Downgrade, current, next);
+    }
+    let old_version = old_version.to_string();
+    let new_version = next.to_string();
+    let new_version = new_version.as_str();
+
+    // Only the bumped files should end up in the commit.
+    let git = git::open(&current_dir);
+    if cmd.commit && !cmd.allow_dirty && git.is_dirty()? {
+        utpm_bail!(DirtyTree);
+    }
 
-    let old_version = config["package"]["version"].as_str().unwrap();
-    let new_version = cmd.new_version.as_str();
-    PackageVersion::from_str(new_version).unwrap();
+    let name = config["package"]["name"].as_str().unwrap_or_default();
+    let package = utils::try_find(&current_dir)?;
+    let extra = Extra::from(package.tool.clone());
+    let settings = BumpConfig::from_tool(&package.tool)?.unwrap_or_default();
+    let replacer = Replacer::new(
+        name,
+        &old_version,
+        new_version,
+        cmd.tag.as_deref().or(settings.tag.as_deref()),
+        &settings.patterns,
+    )?;
 
-    let files = &cmd.include;
+    // Files from the command line, from `[tool.utpm.bump]`, and the examples and
+    // templates importing the package.
+    let files = cmd
+        .include
+        .iter()
+        .cloned()
+        .chain(settings.files)
+        .chain(self_importing_files(&current_dir, &package)?)
+        .unique()
+        .collect::<Vec<_>>();
 
-    for file in files {
-        let mut string = read_to_string(file)?;
+    // Every new content is computed before anything is written, so a failure
+    // doesn't leave the project half bumped.
+    let mut changes = vec![];
+    for file in &files {
+        // Paths are relative to the project, wherever utpm is run from.
+        let path = current_dir.join(file);
+        let before = read_to_string(&path)?;
         utpm_log!(info, "Found {}", file);
-        let old_version = tag_change(cmd.tag.as_deref(), old_version);
-        let new_version = tag_change(cmd.tag.as_deref(), new_version);
-        string = string.replace(old_version.as_ref(), &new_version);
-        if !get_dry_run() {
-            write(file, string)?;
+        let after = replacer.replace(Path::new(file), &before);
+        if get_dry_run() {
+            // Show what would change.
+            for change in diff(file, &before, &after) {
+                utpm_log!(info, change);
+            }
         }
-        utpm_log!(info, "Modified {}", file);
+        changes.push((path, after));
     }
 
-    let files = ["typst.toml"]
+    let mut files: Vec<String> = ["typst.toml".to_string()]
         .into_iter()
-        .chain(files.iter().map(AsRef::<str>::as_ref))
-        .join(", ");
-
-    if !get_dry_run() {
-        config["package"]["version"] = value(new_version);
-        let mut file = File::create(manifest_path)?;
-        write!(file, "{}", config)?;
-        file.sync_all()?;
+        .chain(files)
+        .collect();
+
+    if let Some(changelog) = cmd.changelog.as_ref().or(extra.changelog.as_ref()) {
+        // Like the other files, the changelog is relative to the project.
+        let path = current_dir.join(changelog);
+        changes.push((
+            path.clone(),
+            changelog_content(git.as_ref(), &path, new_version)?,
+        ));
+        files.push(changelog.clone());
+    }
+
+    config["package"]["version"] = value(new_version);
+    if get_dry_run() {
+        for change in diff("typst.toml", &manifest, &config.to_string()) {
+            utpm_log!(info, change);
+        }
+    } else {
+        // The manifest goes last, it only changes once everything else has.
+        changes.push((manifest_path, config.to_string()));
+        write_files(&changes)?;
+        for file in files.iter().skip(1) {
+            utpm_log!(info, "Modified {}", file);
+        }
+    }
+
+    let with_version = |format: &str| format.replace("{version}", new_version);
+    let mut commit = None;
+    let mut git_tag = None;
+    if cmd.commit {
+        let message = with_version(
+            cmd.message
+                .as_deref()
+                .or(settings.message.as_deref())
+                .unwrap_or(DEFAULT_GIT_FORMAT),
+        );
+        if get_dry_run() {
+            utpm_log!(info, "Would commit {} as \"{}\"", files.join(", "), message);
+        } else {
+            for file in &files {
+                git.add(file)?;
+            }
+            git.commit(&message)?;
+        }
+        commit = Some(message);
+    }
+    if cmd.tag_git {
+        let tag = with_version(
+            cmd.tag_format
+                .as_deref()
+                .or(settings.git_tag.as_deref())
+                .unwrap_or(DEFAULT_GIT_FORMAT),
+        );
+        if get_dry_run() {
+            utpm_log!(info, "Would tag {}", tag);
+        } else {
+            git.tag(&tag)?;
+        }
+        git_tag = Some(tag);
     }
 
-    utpm_log!(info, format!("New version: {new_version}"), "version" => new_version, "files" => files);
+    let bumped = Bumped {
+        old_version,
+        new_version: new_version.into(),
+        files,
+        commit,
+        git_tag,
+    };
+    utpm_log!(info, bumped);
     Ok(true)
 }
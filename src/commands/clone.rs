@@ -1,92 +1,51 @@
 use std::{
     borrow::Cow,
     path::{Path, PathBuf},
-    str::FromStr,
 };
 
-use regex::Regex;
 use tracing::instrument;
 use typst_kit::{download::Downloader, package::PackageStorage};
 
 use crate::{
     build,
-    commands::get::get_packages_name_version,
+    commands::get::latest_version,
     path,
     utils::{
-        ProgressPrint, copy_dir_all,
+        ProgressPrint, checksum, copy_dir_all,
         dryrun::get_dry_run,
+        package_ref::{PackageRef, VersionReq},
         paths::{check_path_dir, get_current_dir, has_content, package_cache_path, package_path},
-        state::{Result, UtpmError},
+        state::Result,
+        store::{StoreLock, replace, stage_dir},
         symlink_all,
     },
     utpm_bail, utpm_log,
 };
 
-use typst_syntax::package::{PackageSpec, PackageVersion};
+use typst_syntax::package::PackageSpec;
 
 use super::CloneArgs;
 
-struct RawPkg<'a> {
-    pub namespace: &'a str,
-    pub package: &'a str,
-    pub version: &'a str,
-}
-
-impl<'b> RawPkg<'b> {
-    pub async fn from_str<'a: 'b>(s: &'a str) -> Result<Self> {
-        // Use regex to parse the package specification string.
-        let re_all = Regex::new(r"^@(\w+)\/(\w+):(\d\.\d\.\d)$").unwrap();
-        let re_name = Regex::new(r"^(\w+):(\d\.\d\.\d)$").unwrap();
-        let re_namespace = Regex::new(r"^(\w+)$").unwrap();
-
-        if let Some(cap) = re_all.captures(s) {
-            let (_, [namespace, package, version]) = cap.extract();
-
-            Ok(Self::all(namespace, package, version))
-        } else if let Some(cap) = re_name.captures(s) {
-            let (_, [package, version]) = cap.extract();
-            Ok(Self::pkg(package, version))
-        } else if let Some(cap) = re_namespace.captures(s) {
-            let (_, [package]) = cap.extract();
-            Ok(Self::name(package).await?)
-        } else {
-            utpm_bail!(PackageNotValid);
-        }
-    }
-
-    pub fn all<'a: 'b>(namespace: &'a str, package: &'a str, version: &'a str) -> Self {
-        Self {
-            namespace,
-            package,
-            version,
-        }
-    }
-
-    pub fn pkg<'a: 'b>(package: &'a str, version: &'a str) -> Self {
-        Self {
-            namespace: "preview",
-            package,
-            version,
-        }
-    }
-
-    pub async fn name<'a: 'b>(package: &'a str) -> Result<Self> {
-        let packages = get_packages_name_version().await?;
-        let version = match packages.get(package) {
-            Some(e) => e.version.clone(),
-            None => return Err(UtpmError::PackageNotExist),
-        };
-        let version = Box::leak(version.into_boxed_str());
-        Ok(Self {
-            namespace: "preview",
-            package,
-            version,
-        })
-    }
+/// Downloads a package to `destination` in the cache.
+///
+/// The package is downloaded next to its destination first, then swapped into
+/// place, so a failed download never leaves a partial package behind.
+/// Its checksum is recorded for `pkg verify`.
+pub fn download(spec: &PackageSpec, destination: &Path) -> Result<PathBuf> {
+    let staged = stage_dir(destination)?;
+    let pkg_sto = PackageStorage::new(
+        Some(staged.path().to_path_buf()),
+        None,
+        Downloader::new(format!("utpm/{}", build::COMMIT_HASH)),
+    );
+    let printer = &mut ProgressPrint::new(spec.to_string());
 
-    pub fn parse_version(&self) -> std::result::Result<PackageVersion, ecow::EcoString> {
-        PackageVersion::from_str(self.version)
-    }
+    let Ok(downloaded) = pkg_sto.prepare_package(spec, printer) else {
+        utpm_bail!(PackageNotExist);
+    };
+    replace(&downloaded, destination)?;
+    checksum::save(destination)?;
+    Ok(destination.to_path_buf())
 }
 
 /// Clones a typst package from the official repository or a local path.
@@ -100,8 +59,9 @@ pub async fn run(cmd: &CloneArgs) -> Result<bool> {
         Cow::Owned(get_current_dir()?)
     };
 
-    // Check if the target directory already has content.
-    if has_content(&dst)? {
+    // Check if the target directory already has content. Nothing is written there
+    // when only downloading.
+    if !cmd.download_only && has_content(&dst)? {
         utpm_log!(debug, "found content");
         if cmd.force {
             utpm_log!(warn, "force used, ignore content");
@@ -111,30 +71,42 @@ pub async fn run(cmd: &CloneArgs) -> Result<bool> {
     }
 
     let package = &cmd.package;
-    let pkg = RawPkg::from_str(package).await?;
+    let pkg: PackageRef = package.parse()?;
+    let namespace = pkg.namespace_or("preview");
+    let name = pkg.require_name()?;
+    let version = match pkg.version {
+        VersionReq::Exact(version) => version,
+        req => latest_version(namespace, name, &req).await?,
+    };
+    let spec = pkg.versionless("preview")?.at(version);
 
     // Determine the local path for the package based on its namespace.
-    let local_path = if pkg.namespace == "preview" {
+    let local_path = if namespace == "preview" {
         utpm_log!(info, "preview found, cache dir use");
-        path!(
-            package_cache_path()?,
-            pkg.namespace,
-            pkg.package,
-            pkg.version
-        )
+        path!(package_cache_path()?, namespace, name, version.to_string())
     } else {
         utpm_log!(info, "no preview found, data dir use");
-        path!(package_path()?, pkg.namespace, pkg.package, pkg.version)
+        path!(package_path()?, namespace, name, version.to_string())
     };
 
+    // Only packages from the registry can be downloaded again.
+    let redownload = cmd.redownload && namespace == "preview";
+    if cmd.redownload && !redownload {
+        utpm_log!(
+            warn,
+            "Only @preview packages can be downloaded again, using {}",
+            local_path.display()
+        );
+    }
+
     // If the package already exists locally, copy or symlink it.
-    if check_path_dir(&local_path) {
+    if check_path_dir(&local_path) && !redownload {
         utpm_log!(info, "Package found locally at {}", local_path.display());
         if cmd.download_only {
             utpm_log!(info, "download only, nothing to do.");
-        } else if !cmd.redownload || pkg.namespace != "preview" {
+        } else {
             utpm_log!(info,
-                "namespace" => pkg.namespace,
+                "namespace" => namespace,
                 "redownload" => cmd.redownload
             );
             if cmd.symlink {
@@ -148,36 +120,16 @@ pub async fn run(cmd: &CloneArgs) -> Result<bool> {
         return Ok(true);
     }
 
-    // If the package needs to be downloaded.
-    if cmd.redownload {
-        utpm_log!(warn, "REDOWNLOAD IN WIP");
-        // TODO: Implement removal of the existing directory for redownload.
+    // Download the package. When downloading again, the cached version is only
+    // replaced once the new one is complete.
+    if redownload {
+        utpm_log!(info, "Downloading {} again", spec);
     }
-
-    // Prepare to download the package.
-    let pkg_sto = PackageStorage::new(
-        Some(package_cache_path()?),
-        Some(package_path()?),
-        Downloader::new(format!("utpm/{}", build::COMMIT_HASH)),
-    );
-    let printer = &mut ProgressPrint {};
-
-    // Download the package.
+    let _lock = StoreLock::acquire()?;
     let cloned_path = if !get_dry_run() {
-        pkg_sto.prepare_package(
-            &PackageSpec {
-                namespace: pkg.namespace.into(),
-                name: package.into(),
-                version: pkg.parse_version().unwrap(),
-            },
-            printer,
-        )
+        download(&spec, &local_path)?
     } else {
-        Ok(PathBuf::new())
-    };
-
-    let Ok(cloned_path) = cloned_path else {
-        utpm_bail!(PackageNotExist);
+        PathBuf::new()
     };
 
     utpm_log!(info, "package downloaded", "path" => cloned_path.display().to_string());
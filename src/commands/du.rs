@@ -0,0 +1,156 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use fmt_derive::{Debug, Display};
+use ptree::{TreeItem, print_tree};
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::{
+    utils::{
+        dir_stats, format_size,
+        output::{OutputFormat, get_output_format},
+        paths::{package_cache_path, package_path, utpm_data_path},
+        state::Result,
+    },
+    utpm_log,
+};
+
+use super::DuArgs;
+
+/// Levels below the package stores: namespaces, packages and versions.
+const STORE_DEPTH: usize = 3;
+
+/// Levels below UTPM's data directory.
+const DATA_DEPTH: usize = 1;
+
+/// Disk usage of a directory and, down to the requested depth, of its content.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{}", self.lines(0).join("\n"))]
+pub struct Usage {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Size on disk, in bytes.
+    pub size: u64,
+    /// Whether this is a symlink, only the link itself is counted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub symlink: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Usage>,
+}
+
+impl Usage {
+    /// Measures `path`, detailing its content `depth` levels down.
+    ///
+    /// Children are sorted from the largest to the smallest. Symlinks are not followed,
+    /// like in [`dir_stats`], and a missing path is reported as empty.
+    pub fn read(path: &Path, name: String, depth: usize) -> Result<Self> {
+        let mut usage = Self {
+            name,
+            path: None,
+            size: 0,
+            symlink: false,
+            children: vec![],
+        };
+        let Ok(meta) = path.symlink_metadata() else {
+            return Ok(usage);
+        };
+        if meta.file_type().is_symlink() {
+            usage.symlink = true;
+            usage.size = meta.len();
+        } else if !meta.is_dir() {
+            usage.size = meta.len();
+        } else if depth == 0 {
+            usage.size = dir_stats(path)?.0;
+        } else {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                usage
+                    .children
+                    .push(Self::read(&entry.path(), name, depth - 1)?);
+            }
+            usage.size = usage.children.iter().map(|child| child.size).sum();
+            usage
+                .children
+                .sort_by(|a, b| b.size.cmp(&a.size).then(a.name.cmp(&b.name)));
+        }
+        Ok(usage)
+    }
+
+    /// Describes this entry on a single line, e.g. `cetz 1.2 MiB`.
+    fn describe(&self) -> String {
+        let mut line = format!("{} {}", self.name, format_size(self.size));
+        if let Some(path) = &self.path {
+            line.push_str(&format!(" ({path})"));
+        }
+        if self.symlink {
+            line.push_str(" [symlink]");
+        }
+        line
+    }
+
+    /// Describes this entry and its children, indented by level.
+    fn lines(&self, level: usize) -> Vec<String> {
+        let mut lines = vec![format!("{}{}", "  ".repeat(level), self.describe())];
+        for child in &self.children {
+            lines.extend(child.lines(level + 1));
+        }
+        lines
+    }
+}
+
+impl TreeItem for Usage {
+    type Child = Usage;
+
+    fn write_self<W: std::io::Write>(
+        &self,
+        w: &mut W,
+        _style: &ptree::Style,
+    ) -> std::io::Result<()> {
+        write!(w, "{}", self.describe())
+    }
+
+    fn children(&self) -> Cow<'_, [Usage]> {
+        Cow::Borrowed(&self.children)
+    }
+}
+
+/// Reports the disk usage of the package stores and of UTPM's data directory.
+///
+/// Local packages and the download cache are detailed by namespace, package and
+/// version, the data directory by subdirectory. `--depth` limits how far down
+/// the report goes.
+#[instrument(skip(cmd))]
+pub async fn run(cmd: &DuArgs) -> Result<bool> {
+    utpm_log!(trace, "executing du command");
+    let depth = |max: usize| cmd.depth.map_or(max, |depth| depth.min(max));
+    let stores = [
+        ("local", package_path()?, depth(STORE_DEPTH)),
+        ("cache", package_cache_path()?, depth(STORE_DEPTH)),
+        ("data", utpm_data_path()?, depth(DATA_DEPTH)),
+    ];
+
+    let mut children = vec![];
+    for (name, path, depth) in stores {
+        let mut usage = Usage::read(&path, name.into(), depth)?;
+        usage.path = Some(path.display().to_string());
+        children.push(usage);
+    }
+    let usage = Usage {
+        name: "total".into(),
+        path: None,
+        size: children.iter().map(|child| child.size).sum(),
+        symlink: false,
+        children,
+    };
+
+    if get_output_format() == OutputFormat::Text {
+        print_tree(&usage)?;
+    } else {
+        utpm_log!(info, usage);
+    }
+    Ok(true)
+}
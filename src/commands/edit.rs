@@ -0,0 +1,241 @@
+use std::fs::{read_to_string, write};
+
+use toml_edit::{Array, DocumentMut, Item, Table, TableLike, Value};
+use tracing::instrument;
+use typst_syntax::package::PackageManifest;
+
+use crate::{
+    utils::{
+        dryrun::get_dry_run,
+        paths::get_current_dir,
+        state::{Result, UtpmError},
+        try_find_path,
+        universe::{check_category, check_discipline},
+    },
+    utpm_bail, utpm_log,
+};
+
+use super::{EditListArgs, SetArgs};
+
+/// The tables at the root of typst.toml.
+const ROOT_TABLES: &[&str] = &["package", "template", "tool"];
+
+/// Splits a dotted key into its parts.
+///
+/// Keys that don't start with a root table are `[package]` keys, so `description`
+/// is the same as `package.description`.
+pub fn key_path(key: &str) -> Result<Vec<&str>> {
+    let mut path: Vec<&str> = key.split('.').collect();
+    if let Some(part) = path.iter().find(|part| {
+        part.is_empty()
+            || !part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }) {
+        utpm_bail!(
+            ManifestEdit,
+            key.into(),
+            format!("`{part}` is not a valid key")
+        );
+    }
+    if !ROOT_TABLES.contains(&path[0]) {
+        path.insert(0, "package");
+    }
+    Ok(path)
+}
+
+/// Returns the item at `key`, creating the tables leading to it.
+fn item_mut<'a>(doc: &'a mut DocumentMut, key: &str) -> Result<&'a mut Item> {
+    let path = key_path(key)?;
+    let (last, parents) = path.split_last().expect("a key has at least one part");
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for (i, part) in parents.iter().enumerate() {
+        let item = table.entry(part).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        let Some(inner) = item.as_table_like_mut() else {
+            utpm_bail!(
+                ManifestEdit,
+                key.into(),
+                format!("`{}` is not a table", path[..=i].join("."))
+            );
+        };
+        table = inner;
+    }
+    Ok(table.entry(last).or_insert(Item::None))
+}
+
+/// Returns the array at `key`, creating it if needed.
+fn array_mut<'a>(doc: &'a mut DocumentMut, key: &str) -> Result<&'a mut Array> {
+    let item = item_mut(doc, key)?;
+    if item.is_none() {
+        *item = Item::Value(Value::Array(Array::new()));
+    }
+    match item.as_array_mut() {
+        Some(array) => Ok(array),
+        None => utpm_bail!(
+            ManifestEdit,
+            key.into(),
+            "not an array, use `prj set` instead".into()
+        ),
+    }
+}
+
+/// Checks the values given to `categories` and `disciplines`.
+fn check_list(key: &str, values: &[&str]) -> Result<()> {
+    let check = match key_path(key)?[..] {
+        ["package", "categories"] => check_category,
+        ["package", "disciplines"] => check_discipline,
+        _ => return Ok(()),
+    };
+    values.iter().try_for_each(|value| check(value))
+}
+
+/// Sets `key` to `value`, keeping the comments around the previous value.
+pub fn set_value(doc: &mut DocumentMut, key: &str, mut value: Value) -> Result<()> {
+    let values: Vec<&str> = match &value {
+        Value::Array(array) => array.iter().filter_map(Value::as_str).collect(),
+        value => value.as_str().into_iter().collect(),
+    };
+    check_list(key, &values)?;
+
+    let item = item_mut(doc, key)?;
+    if item.is_table_like() {
+        utpm_bail!(ManifestEdit, key.into(), "it is a table".into());
+    }
+    if let Some(old) = item.as_value() {
+        *value.decor_mut() = old.decor().clone();
+    }
+    *item = match value {
+        // Root tables like `[template]` are written as sections.
+        Value::InlineTable(table) if key_path(key)?.len() == 1 => Item::Table(table.into_table()),
+        value => Item::Value(value),
+    };
+    Ok(())
+}
+
+/// Appends the `values` missing from the array at `key`.
+///
+/// Returns the values that were added.
+pub fn add_values(doc: &mut DocumentMut, key: &str, values: &[String]) -> Result<Vec<String>> {
+    check_list(key, &values.iter().map(String::as_str).collect::<Vec<_>>())?;
+    let array = array_mut(doc, key)?;
+    let mut added = vec![];
+    for value in values {
+        if !array.iter().any(|item| item.as_str() == Some(value)) && !added.contains(value) {
+            array.push(value.as_str());
+            added.push(value.clone());
+        }
+    }
+    Ok(added)
+}
+
+/// Removes the `values` from the array at `key`.
+///
+/// Returns the values that were removed.
+pub fn remove_values(doc: &mut DocumentMut, key: &str, values: &[String]) -> Result<Vec<String>> {
+    if item_mut(doc, key)?.is_none() {
+        return Ok(vec![]);
+    }
+    let array = array_mut(doc, key)?;
+    // The first item keeps its spacing when the previous one is removed.
+    let prefix = array.get(0).and_then(|item| item.decor().prefix().cloned());
+    let mut removed = vec![];
+    array.retain(|item| match item.as_str() {
+        Some(item) if values.iter().any(|value| value == item) => {
+            removed.push(item.to_string());
+            false
+        },
+        _ => true,
+    });
+    if let (Some(prefix), Some(first)) = (prefix, array.get_mut(0)) {
+        first.decor_mut().set_prefix(prefix);
+    }
+    Ok(removed)
+}
+
+/// Fails if the edited manifest doesn't match the typst.toml schema.
+pub fn validate(doc: &DocumentMut, key: &str) -> Result<()> {
+    toml::from_str::<PackageManifest>(&doc.to_string()).map_err(|err| {
+        UtpmError::ManifestEdit(
+            key.into(),
+            format!("the manifest would be invalid: {}", err.message()),
+        )
+    })?;
+    Ok(())
+}
+
+/// Applies `edit` to the manifest of the current project and saves it.
+///
+/// Respects dry-run mode - if dry-run is enabled, the manifest isn't written.
+fn edit_manifest(key: &str, edit: impl FnOnce(&mut DocumentMut) -> Result<()>) -> Result<()> {
+    let manifest_path = try_find_path(get_current_dir()?)?;
+    let mut doc = read_to_string(&manifest_path)?.parse::<DocumentMut>()?;
+    edit(&mut doc)?;
+    validate(&doc, key)?;
+    if !get_dry_run() {
+        write(&manifest_path, doc.to_string())?;
+    }
+    Ok(())
+}
+
+/// Sets a value of typst.toml.
+///
+/// The value is a string, unless `--toml` is used. Comments and formatting of the
+/// manifest are preserved.
+#[instrument(skip(cmd))]
+pub async fn run_set(cmd: &SetArgs) -> Result<bool> {
+    utpm_log!(trace, "executing set command");
+    let value = if cmd.toml {
+        cmd.value.parse::<Value>().map_err(|_| {
+            UtpmError::ManifestEdit(
+                cmd.key.clone(),
+                format!("`{}` is not a TOML value", cmd.value),
+            )
+        })?
+    } else {
+        Value::from(cmd.value.as_str())
+    };
+    let shown = value.to_string();
+    edit_manifest(&cmd.key, |doc| set_value(doc, &cmd.key, value))?;
+    utpm_log!(info, "Set {} to {}", cmd.key, shown.trim());
+    Ok(true)
+}
+
+/// Adds values to an array of typst.toml, like `keywords` or `authors`.
+#[instrument(skip(cmd))]
+pub async fn run_add(cmd: &EditListArgs) -> Result<bool> {
+    utpm_log!(trace, "executing add command");
+    let mut added = vec![];
+    edit_manifest(&cmd.key, |doc| {
+        added = add_values(doc, &cmd.key, &cmd.values)?;
+        Ok(())
+    })?;
+    for value in cmd.values.iter().filter(|value| !added.contains(value)) {
+        utpm_log!(warn, "{} is already in {}", value, cmd.key);
+    }
+    if !added.is_empty() {
+        utpm_log!(info, "Added {} to {}", added.join(", "), cmd.key);
+    }
+    Ok(true)
+}
+
+/// Removes values from an array of typst.toml.
+#[instrument(skip(cmd))]
+pub async fn run_remove(cmd: &EditListArgs) -> Result<bool> {
+    utpm_log!(trace, "executing remove command");
+    let mut removed = vec![];
+    edit_manifest(&cmd.key, |doc| {
+        removed = remove_values(doc, &cmd.key, &cmd.values)?;
+        Ok(())
+    })?;
+    for value in cmd.values.iter().filter(|value| !removed.contains(value)) {
+        utpm_log!(warn, "{} is not in {}", value, cmd.key);
+    }
+    if !removed.is_empty() {
+        utpm_log!(info, "Removed {} from {}", removed.join(", "), cmd.key);
+    }
+    Ok(true)
+}
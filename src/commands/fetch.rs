@@ -0,0 +1,225 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use fmt_derive::{Debug, Display};
+use itertools::Itertools;
+use serde::{Serialize, Serializer};
+use tracing::instrument;
+use typst_syntax::package::PackageSpec;
+
+use crate::{
+    path,
+    utils::{
+        copy_dir_all,
+        dryrun::get_dry_run,
+        imports::scan_imports,
+        paths::{get_current_dir, package_cache_path, package_path},
+        state::Result,
+        store::{StoreLock, remove_path},
+    },
+    utpm_bail, utpm_log,
+};
+
+use super::{FetchArgs, clone::download};
+
+/// Where a dependency was found.
+#[derive(Serialize, Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Downloaded into the cache by this run.
+    #[display("fetched")]
+    Fetched,
+    /// Already in the cache.
+    #[display("cached")]
+    Cached,
+    /// In the local packages, e.g. linked or installed.
+    #[display("local")]
+    Local,
+    /// Neither available nor downloadable.
+    #[display("missing")]
+    Missing,
+}
+
+/// A package the project depends on, directly or through another package.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{status} {spec}")]
+pub struct Dependency {
+    #[serde(serialize_with = "serialize_spec")]
+    pub spec: PackageSpec,
+    pub status: Status,
+    /// Where the package lives, if it is available.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+fn serialize_spec<S: Serializer>(spec: &PackageSpec, s: S) -> std::result::Result<S::Ok, S::Error> {
+    s.collect_str(spec)
+}
+
+/// The result of `prj fetch`.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{}{} package(s), {} fetched, {} missing{}", packages.iter().map(|p| format!("{p}\n")).join(""), packages.len(), self.count(Status::Fetched), self.count(Status::Missing), vendor.as_ref().map(|dir| format!(", vendored into {dir}")).unwrap_or_default())]
+pub struct Report {
+    pub packages: Vec<Dependency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+}
+
+impl Report {
+    fn count(&self, status: Status) -> usize {
+        self.packages.iter().filter(|p| p.status == status).count()
+    }
+}
+
+/// Downloads every package a project needs, so it can be compiled offline.
+///
+/// Imports are collected from the given project directories or Typst files, then
+/// from the packages they import, and so on. `@preview` packages missing from
+/// both stores are downloaded into the cache. With `--vendor`, every dependency
+/// is also copied into a package directory usable as `TYPST_PACKAGE_PATH`.
+#[instrument(skip(cmd))]
+pub async fn run(cmd: &FetchArgs) -> Result<bool> {
+    utpm_log!(trace, "executing fetch command");
+    let roots = if cmd.paths.is_empty() {
+        vec![get_current_dir()?]
+    } else {
+        cmd.paths.clone()
+    };
+
+    let mut imports = HashSet::new();
+    for root in &roots {
+        imports.extend(scan_imports(root)?);
+    }
+    if imports.is_empty() {
+        utpm_log!(
+            warn,
+            "No import found in {}",
+            roots.iter().map(|root| root.display()).join(", ")
+        );
+    }
+
+    let _lock = if get_dry_run() {
+        None
+    } else {
+        Some(StoreLock::acquire()?)
+    };
+    let cache = package_cache_path()?;
+    // A failed download is reported as missing, the other packages are still fetched.
+    let packages = resolve(imports, &package_path()?, &cache, |spec, dir| {
+        utpm_log!(info, "Downloading {}...", spec);
+        if !get_dry_run()
+            && let Err(err) = download(spec, dir)
+        {
+            utpm_log!(warn, "Can't download {}: {}", spec, err);
+            return Ok(Status::Missing);
+        }
+        Ok(Status::Fetched)
+    })?;
+    for dependency in &packages {
+        if dependency.status == Status::Missing && dependency.spec.namespace != "preview" {
+            utpm_log!(
+                warn,
+                "{} is not installed and can't be downloaded",
+                dependency.spec
+            );
+        }
+    }
+
+    if let Some(vendor) = &cmd.vendor {
+        vendor_all(&packages, vendor)?;
+    }
+    let report = Report {
+        packages,
+        vendor: cmd.vendor.as_ref().map(|dir| dir.display().to_string()),
+    };
+    utpm_log!(info, report);
+    let missing = report
+        .packages
+        .iter()
+        .filter(|p| p.status == Status::Missing)
+        .map(|p| p.spec.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        utpm_bail!(MissingPackages, missing.join(", "));
+    }
+    Ok(true)
+}
+
+/// Finds every package needed by `imports`, directly or transitively.
+///
+/// Packages are looked up in `local` first, then in `cache`, like Typst does.
+/// Missing `@preview` packages are given to `fetch` with their destination in the
+/// cache, which tells whether they have been fetched or are still missing. The
+/// imports of a fetched package are only followed if it is there afterwards,
+/// which isn't the case in dry-run mode.
+pub fn resolve(
+    imports: HashSet<PackageSpec>,
+    local: &Path,
+    cache: &Path,
+    mut fetch: impl FnMut(&PackageSpec, &Path) -> Result<Status>,
+) -> Result<Vec<Dependency>> {
+    let mut seen = imports.clone();
+    let mut queue: VecDeque<PackageSpec> = imports.into_iter().collect();
+    let mut packages = vec![];
+
+    while let Some(spec) = queue.pop_front() {
+        let version = spec.version.to_string();
+        let in_local: PathBuf = path!(local, spec.namespace.as_str(), spec.name.as_str(), &version);
+        let in_cache: PathBuf = path!(cache, spec.namespace.as_str(), spec.name.as_str(), &version);
+
+        let (status, path) = if in_local.is_dir() {
+            (Status::Local, Some(in_local))
+        } else if in_cache.is_dir() {
+            (Status::Cached, Some(in_cache))
+        } else if spec.namespace != "preview" {
+            (Status::Missing, None)
+        } else {
+            let status = fetch(&spec, &in_cache)?;
+            let fetched = status == Status::Fetched && in_cache.is_dir();
+            (status, fetched.then_some(in_cache))
+        };
+
+        // Follow the imports of the package itself.
+        if let Some(path) = &path {
+            for import in scan_imports(path)? {
+                if seen.insert(import.clone()) {
+                    queue.push_back(import);
+                }
+            }
+        }
+        packages.push(Dependency { spec, status, path });
+    }
+
+    packages.sort_by(|a, b| {
+        (&a.spec.namespace, &a.spec.name, a.spec.version).cmp(&(
+            &b.spec.namespace,
+            &b.spec.name,
+            b.spec.version,
+        ))
+    });
+    Ok(packages)
+}
+
+/// Copies the available dependencies into `vendor`, laid out like a package directory.
+///
+/// Respects dry-run mode - if dry-run is enabled, nothing is copied.
+fn vendor_all(packages: &[Dependency], vendor: &Path) -> Result<()> {
+    for dependency in packages {
+        let Some(path) = &dependency.path else {
+            continue;
+        };
+        let spec = &dependency.spec;
+        let target: PathBuf = path!(
+            vendor,
+            spec.namespace.as_str(),
+            spec.name.as_str(),
+            spec.version.to_string()
+        );
+        utpm_log!(debug, "Vendoring {} into {}", spec, target.display());
+        if !get_dry_run() {
+            remove_path(&target)?;
+            copy_dir_all(path, &target)?;
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,21 @@
-use std::collections::HashMap;
+use std::fs::read_dir;
+use std::path::PathBuf;
 
 use fmt_derive::Display;
 use serde::{Deserialize, Serialize};
 use toml::to_string_pretty;
 use tracing::instrument;
+use typst_syntax::package::PackageVersion;
 
-use crate::{build, utils::state::Result, utpm_log};
+use crate::{
+    build, path,
+    utils::{
+        package_ref::{PackageRef, VersionReq},
+        paths::package_path,
+        state::{Result, UtpmError},
+    },
+    utpm_bail, utpm_log,
+};
 
 use super::GetArgs;
 
@@ -52,29 +62,40 @@ pub async fn get_all_packages() -> Result<Vec<RawPackage>> {
     Ok(packages)
 }
 
-/// Fetches all packages and creates a hashmap for lookup by name or name:version.
+/// Finds the latest version of a package matching `req`.
 ///
-/// # Returns
-/// A hashmap where keys can be either:
-/// - Package name (e.g., "mypackage") - returns the latest version
-/// - Package name with version (e.g., "mypackage:1.0.0") - returns that specific version
+/// `@preview` packages are looked up in Typst Universe, other namespaces in the
+/// local packages.
 ///
 /// # Errors
-/// Returns an error if the HTTP request fails or the response cannot be parsed.
-pub async fn get_packages_name_version() -> Result<HashMap<String, RawPackage>> {
-    let packages: Vec<RawPackage> = get_all_packages().await?;
-    let mut hashmap: HashMap<String, RawPackage> = HashMap::with_capacity(packages.len() * 2);
-
-    for pkg in packages {
-        // Insert by name (latest version)
-        let name = pkg.name.clone();
-        let version_key = format!("{}:{}", pkg.name, pkg.version);
-
-        hashmap.insert(name, pkg.clone());
-        hashmap.insert(version_key, pkg);
-    }
-
-    Ok(hashmap)
+/// Returns [`UtpmError::PackageNotExist`] if no version matches.
+pub async fn latest_version(
+    namespace: &str,
+    name: &str,
+    req: &VersionReq,
+) -> Result<PackageVersion> {
+    let versions: Vec<PackageVersion> = if namespace == "preview" {
+        get_all_packages()
+            .await?
+            .into_iter()
+            .filter(|pkg| pkg.name == name)
+            .filter_map(|pkg| pkg.version.parse().ok())
+            .collect()
+    } else {
+        let dir: PathBuf = path!(package_path()?, namespace, name);
+        if !dir.is_dir() {
+            utpm_bail!(PackageNotExist);
+        }
+        read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+            .collect()
+    };
+    versions
+        .into_iter()
+        .filter(|version| req.matches(version))
+        .max()
+        .ok_or(UtpmError::PackageNotExist)
 }
 
 /// Retrieves and displays package information from the Typst Universe registry.
@@ -84,22 +105,32 @@ pub async fn get_packages_name_version() -> Result<HashMap<String, RawPackage>>
 #[instrument(skip(cmd))]
 pub async fn run(cmd: &GetArgs) -> Result<bool> {
     utpm_log!(trace, "executing get command");
-    if !cmd.packages.is_empty() {
-        let packages: HashMap<String, RawPackage> = get_packages_name_version().await?;
-        for e in &cmd.packages {
-            if !packages.contains_key(e) {
-                utpm_log!(warn, "Package not found", "input" => e);
-                continue;
-            }
-            let package = packages.get(e).unwrap();
-            utpm_log!(info, package);
-        }
-    } else {
-        let packages: Vec<_> = get_all_packages().await?;
+    let packages: Vec<_> = get_all_packages().await?;
+    if cmd.packages.is_empty() {
         for package in packages {
             utpm_log!(info, package);
         }
+        return Ok(true);
     }
 
+    for input in &cmd.packages {
+        let pkg: PackageRef = input.parse()?;
+        let name = pkg.require_name()?;
+        if pkg.namespace_or("preview") != "preview" {
+            utpm_log!(warn, "Only @preview packages are in Typst Universe", "input" => input);
+            continue;
+        }
+        // The latest version matching the request.
+        let found = packages
+            .iter()
+            .filter(|package| package.name == name)
+            .filter_map(|package| Some((package.version.parse::<PackageVersion>().ok()?, package)))
+            .filter(|(version, _)| pkg.version.matches(version))
+            .max_by_key(|(version, _)| *version);
+        match found {
+            Some((_, package)) => utpm_log!(info, package),
+            None => utpm_log!(warn, "Package not found", "input" => input),
+        }
+    }
     Ok(true)
 }
@@ -6,7 +6,7 @@ use std::{
     str::FromStr,
 };
 
-use inquire::{Select, Text, required, validator::Validation};
+use inquire::{MultiSelect, Select, Text, required, validator::Validation};
 use toml::Table;
 use tracing::instrument;
 use typst_syntax::package::{PackageInfo, PackageManifest, PackageVersion, ToolInfo, VersionBound};
@@ -17,6 +17,7 @@ use crate::{
         paths::{check_path_file, get_current_dir},
         specs::Extra,
         state::Result,
+        universe::{CATEGORIES, DISCIPLINES, check_category, check_discipline},
         write_manifest,
     },
     utpm_bail, utpm_log,
@@ -181,13 +182,35 @@ fn interactive_pkg_info(cmd: &mut InitArgs) -> Result<PackageInfo> {
             .filter(|f| !f.trim().is_empty())
             .map(|f| f.trim().into())
             .collect::<Vec<_>>();
+
+        pkg.categories = MultiSelect::new("Categories: ", CATEGORIES.to_vec())
+            .with_help_message("Where your package is listed on Typst Universe")
+            .prompt()?
+            .into_iter()
+            .map(|f| f.into())
+            .collect::<Vec<_>>();
+
+        pkg.disciplines = MultiSelect::new("Disciplines: ", DISCIPLINES.to_vec())
+            .with_help_message("Who your package is for, leave empty if it is for everyone")
+            .prompt()?
+            .into_iter()
+            .map(|f| f.into())
+            .collect::<Vec<_>>();
     }
 
     Ok(pkg)
 }
 
 /// Build the package metadata from command-line arguments.
+///
+/// Categories and disciplines must be the ones of Typst Universe.
 fn cmd_pkg_info(cmd: &InitArgs) -> Result<PackageInfo> {
+    for category in cmd.categories.iter().flatten() {
+        check_category(category)?;
+    }
+    for discipline in cmd.disciplines.iter().flatten() {
+        check_discipline(discipline)?;
+    }
     Ok(PackageInfo {
         name: <std::option::Option<std::string::String> as Clone>::clone(&cmd.name)
             .unwrap()
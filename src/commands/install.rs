@@ -1,13 +1,17 @@
 use std::fs;
+use std::path::Path;
 
 use crate::{
     commands::LinkArgs,
     utils::{
         copy_dir_all,
         dryrun::get_dry_run,
-        git::{clone_git, exist_git, project},
-        paths::{MANIFEST_FILE, check_path_dir, check_path_file, package_path, utpm_data_path},
+        git::{self, exist_git},
+        package_ref::PackageRef,
+        paths::{MANIFEST_FILE, check_path_dir, check_path_file, package_path},
+        provenance::{LinkedCopy, Provenance, VersionRecord},
         state::Result,
+        store::temp_dir,
         try_find,
     },
     utpm_log,
@@ -16,6 +20,65 @@ use crate::{
 use super::{InstallArgs, link};
 use tracing::instrument;
 
+/// Returns whether a source is a git, http(s) or file URL rather than a local path.
+pub fn is_remote(url: &str) -> bool {
+    url.starts_with("git") || url.starts_with("http") || url.starts_with("file://")
+}
+
+/// Arguments used to link a fetched package: every file is copied, ignore files are not read.
+fn link_args(namespace: Option<String>) -> LinkArgs {
+    LinkArgs {
+        force: false,
+        no_copy: false,
+        watch: false,
+        as_version: None,
+        alias: None,
+        namespace,
+        git_exclude: false,
+        git_global_ignore: false,
+        git_ignore: false,
+        ignore: false,
+        typst_ignore: false,
+    }
+}
+
+/// Links the package fetched into `path` as `@namespace/name:version`.
+///
+/// The fetched copy is deleted afterwards, so the package isn't recorded as a
+/// copy made by `prj link`.
+pub(crate) async fn link_fetched(
+    path: &Path,
+    namespace: &str,
+    name: &str,
+    version: &str,
+) -> Result<()> {
+    let lnk = link_args(Some(namespace.to_string()));
+    link::run(&lnk, &Some(path.display().to_string()), false).await?;
+    LinkedCopy::remove(namespace, name, version)
+}
+
+/// Fetches the source of a package into `path`, which must be an empty directory.
+///
+/// Git, http(s) and file URLs are cloned, then `reference` is checked out if given.
+/// Anything else is treated as a local path and copied.
+///
+/// # Returns
+/// The commit checked out, or `None` for local paths.
+pub fn fetch_source(url: &str, reference: Option<&str>, path: &Path) -> Result<Option<String>> {
+    if !is_remote(url) {
+        copy_dir_all(url, path)?;
+        return Ok(None);
+    }
+
+    let git = git::open(path);
+    git.clone(url, path)?;
+    if let Some(reference) = reference {
+        utpm_log!(debug, "Checking out {}", reference);
+        git.checkout(reference)?;
+    }
+    Ok(Some(git.head_commit()?))
+}
+
 /// Installs a package from a git repository.
 ///
 /// Clones the repository to a temporary directory, validates the package structure,
@@ -25,6 +88,12 @@ use tracing::instrument;
 /// This command requires git to be installed and cannot run in dry-run mode.
 #[instrument]
 pub async fn run(cmd: &InstallArgs) -> Result<bool> {
+    if let Some(namespace) = &cmd.namespace {
+        let pkg: PackageRef = format!("@{namespace}").parse()?;
+        if pkg.name.is_some() {
+            return Err(pkg.invalid("the namespace can't contain a package name"));
+        }
+    }
     if get_dry_run() {
         utpm_log!(warn, "Dry-run, can't do anything");
         return Ok(true);
@@ -34,10 +103,9 @@ pub async fn run(cmd: &InstallArgs) -> Result<bool> {
 
     utpm_log!(trace, "executing init command for install");
 
-    let path = utpm_data_path()?.join("tmp");
-    if check_path_dir(&path) && !get_dry_run() {
-        fs::remove_dir_all(&path)?;
-    }
+    // Each install gets its own temporary directory, removed when it goes out of scope.
+    let tmp = temp_dir()?;
+    let path = tmp.path().to_path_buf();
 
     // Determine the source path for the installation.
     utpm_log!(
@@ -48,19 +116,9 @@ pub async fn run(cmd: &InstallArgs) -> Result<bool> {
     );
 
     // If a URL is provided, clone or copy the repository.
-    fs::create_dir_all(&path)?;
-
-    project().lock().unwrap().0 = path.clone();
-
     let url = &cmd.url;
+    let commit = fetch_source(url, cmd.reference.as_deref(), &path)?;
 
-    // Handle git and http(s) URLs.
-    if url.starts_with("git") || url.starts_with("http") {
-        clone_git(url, &path.to_string_lossy())?;
-    } else {
-        // Handle local paths.
-        copy_dir_all(url, &path)?;
-    }
     // Check for a manifest file in the source directory.
     let typstfile = path.join(MANIFEST_FILE);
     if !check_path_file(&typstfile) {
@@ -73,6 +131,21 @@ pub async fn run(cmd: &InstallArgs) -> Result<bool> {
     let file = try_find(&path)?;
     let namespace = cmd.namespace.as_deref().unwrap_or("local");
     utpm_log!(trace, "After loading manifest...");
+
+    // Remember where the package comes from so `pkg update` can fetch it again.
+    // Local sources are stored as absolute paths.
+    let source = match commit {
+        Some(_) => url.clone(),
+        None => fs::canonicalize(url)?.display().to_string(),
+    };
+    let mut record = Provenance::load(namespace, &file.package.name)?.unwrap_or_else(|| {
+        Provenance::new(namespace, file.package.name.as_str(), &source, None, None)
+    });
+    record.source = source;
+    record.reference = cmd.reference.clone();
+    record.commit = commit;
+    record.add_version(file.package.version.to_string());
+
     // Check if the package is already installed.
     if check_path_dir(format!(
         "{}/{}/{}/{}",
@@ -85,25 +158,21 @@ pub async fn run(cmd: &InstallArgs) -> Result<bool> {
             "{}",
             format!("~ {}:{}", file.package.name, file.package.version)
         );
+        record.save()?;
         return Ok(true);
     }
 
     utpm_log!("{}", format!("Installing {}...", file.package.name));
 
-    // Link the installed package and clean up temporary files.
-    let lnk = LinkArgs {
-        force: false,
-        no_copy: false,
-        namespace: cmd.namespace.clone(),
-        git_exclude: false,
-        git_global_ignore: false,
-        git_ignore: false,
-        ignore: false,
-        typst_ignore: false,
-    };
-
-    link::run(&lnk, &Some(path.display().to_string()), false).await?;
-    fs::remove_dir_all(&path)?;
+    // Link the installed package, the temporary files are cleaned up on drop.
+    link_fetched(
+        &path,
+        namespace,
+        &file.package.name,
+        &file.package.version.to_string(),
+    )
+    .await?;
+    record.save()?;
 
     utpm_log!(info, "+ {}:{}", file.package.name, file.package.version);
     Ok(true)
@@ -1,25 +1,42 @@
 use ignore::{WalkBuilder, overrides::OverrideBuilder};
+use std::collections::BTreeMap;
 use std::fs::{copy, create_dir_all};
+use std::path::Path;
 use std::path::PathBuf;
-use std::{fs, path::Path};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 use tracing::instrument;
+use typst_syntax::package::PackageVersion;
 
 use crate::{
     path,
     utils::{
+        checksum,
         dryrun::get_dry_run,
         paths::{
             check_path_dir, check_path_file, get_current_dir, package_cache_path, package_path,
         },
+        provenance::{DevLink, LinkedCopy, VersionRecord},
         specs::Extra,
         state::Result,
-        symlink_all, try_find,
+        store::{StoreLock, commit_staged, commit_symlink, remove_path, stage_dir},
+        try_find,
     },
     utpm_bail, utpm_log,
 };
 
 use super::LinkArgs;
 
+/// How often the project is checked for changes in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The first version used for development links given an alias but no version.
+const DEV_VERSION: PackageVersion = PackageVersion {
+    major: 0,
+    minor: 0,
+    patch: 0,
+};
+
 /// Links the current project to the local package directory, either by copying or symlinking.
 #[instrument(skip(cmd))]
 pub async fn run(cmd: &LinkArgs, path: &Option<String>, pt: bool) -> Result<bool> {
@@ -33,46 +50,42 @@ pub async fn run(cmd: &LinkArgs, path: &Option<String>, pt: bool) -> Result<bool
 
     // Load the manifest and determine the namespace.
     let config = try_find(&curr)?;
+    let extra = Extra::from(config.tool);
     let namespace = cmd.namespace.clone().unwrap_or("local".into());
 
     // Construct the destination path for the package.
+    // Development links may sit next to the released version under another one.
     let name = config.package.name;
-    let version = config.package.version;
-    let destination = if namespace != "preview" {
-        path!(
-            package_path()?,
-            &namespace,
-            name.as_ref(),
-            version.to_string()
-        )
+    let dev = cmd.as_version.is_some() || cmd.alias.is_some();
+    let root = if namespace != "preview" {
+        package_path()?
     } else {
-        path!(
-            package_cache_path()?,
-            &namespace,
-            name.as_ref(),
-            version.to_string()
-        )
+        package_cache_path()?
+    };
+    let package = path!(root, &namespace, name.as_ref());
+    let version = match (cmd.as_version, &cmd.alias) {
+        (Some(version), _) => version,
+        (None, Some(alias)) => alias_version(&package, &namespace, &name, alias)?,
+        (None, None) => config.package.version,
     };
+    let destination = path!(package, version.to_string());
+
+    // Keep other utpm processes out of the store until the package is in place.
+    let lock = StoreLock::acquire()?;
 
     // Check if the package already exists at the destination.
     if check_path_dir(&destination) && !cmd.force {
         utpm_bail!(AlreadyExist, name.to_string(), version, "Info:".to_string());
     }
 
-    if !get_dry_run() {
-        fs::create_dir_all(destination.parent().unwrap())?
-    };
-
-    // If force is used, remove the existing directory.
-    if cmd.force && !get_dry_run() {
-        fs::remove_dir_all(&destination)?
-    }
-
     // Create a symlink or copy the directory.
     if cmd.no_copy {
         if !get_dry_run() {
-            symlink_all(&curr, &destination)?
+            // If force is used, the existing directory is replaced.
+            commit_symlink(&curr, &destination)?;
         };
+        // The content of a symlink changes with the project, there is nothing to record.
+        checksum::remove(&destination)?;
         if pt {
             utpm_log!(
                 info,
@@ -84,46 +97,25 @@ pub async fn run(cmd: &LinkArgs, path: &Option<String>, pt: bool) -> Result<bool
             );
         }
     } else {
+        utpm_log!(info,
+            "git_ignore" => cmd.git_ignore,
+            "git_global_ignore" => cmd.git_global_ignore,
+            "git_exclude" => cmd.git_exclude
+        );
         if !get_dry_run() {
-            // Use WalkBuilder to respect ignore files.
-            let mut wb: WalkBuilder = WalkBuilder::new(&curr);
-            let mut overr: OverrideBuilder = OverrideBuilder::new(&curr);
-
-            // Add excludes from the manifest to the override builder.
-            if let Some(excludes) = Extra::from(config.tool).exclude {
-                for exclude in excludes.iter() {
-                    overr.add(&format!("!{}", exclude))?;
-                }
-            }
-            wb.overrides(overr.build()?);
-
-            // Configure which ignore files to use.
-            wb.ignore(cmd.ignore)
-                .git_ignore(cmd.git_ignore)
-                .git_global(cmd.git_global_ignore)
-                .git_exclude(cmd.git_exclude);
-            utpm_log!(info,
-                "git_ignore" => cmd.git_ignore,
-                "git_global_ignore" => cmd.git_global_ignore,
-                "git_exclude" => cmd.git_exclude
-            );
-
-            // Add .typstignore if it exists and is enabled.
-            if cmd.typst_ignore {
-                let pathbuf = curr.join(".typstignore");
-                if check_path_file(pathbuf) {
-                    utpm_log!(info, "Added .typstignore");
-                    wb.add_custom_ignore_filename(".typstignore");
-                }
-            }
+            // Files are copied next to the destination first, then moved into place at once.
+            let staged = stage_dir(&destination)?;
 
             // --- Copy Files ---
-            for result in wb.build().collect::<std::result::Result<Vec<_>, _>>()? {
+            for result in walker(cmd, &curr, &extra)?
+                .build()
+                .collect::<std::result::Result<Vec<_>, _>>()?
+            {
                 if let Some(file_type) = result.file_type() {
                     let path: &Path = result.path();
                     let relative = path.strip_prefix(&curr).unwrap();
-                    let dest_path = destination.join(relative);
-                    utpm_log!("{}", dest_path.display());
+                    let dest_path = staged.path().join(relative);
+                    utpm_log!("{}", destination.join(relative).display());
                     if file_type.is_dir() {
                         create_dir_all(&dest_path)?;
                     } else {
@@ -131,7 +123,11 @@ pub async fn run(cmd: &LinkArgs, path: &Option<String>, pt: bool) -> Result<bool
                     }
                 }
             }
+
+            // If force is used, the existing directory is only removed once the copy is in place.
+            commit_staged(staged, &destination)?;
         };
+        checksum::save(&destination)?;
         if pt {
             utpm_log!(
                 info,
@@ -143,5 +139,207 @@ pub async fn run(cmd: &LinkArgs, path: &Option<String>, pt: bool) -> Result<bool
             );
         }
     }
+
+    // Remember development links so `pkg list` can tell them apart.
+    if dev {
+        DevLink {
+            namespace: namespace.clone(),
+            name: name.to_string(),
+            version: version.to_string(),
+            manifest_version: config.package.version.to_string(),
+            alias: cmd.alias.clone(),
+            source: curr.display().to_string(),
+        }
+        .save()?;
+    } else {
+        DevLink::remove(&namespace, &name, &version.to_string())?;
+    }
+    // Copies are recorded too, so they aren't taken for downloads in the cache.
+    if cmd.no_copy {
+        LinkedCopy::remove(&namespace, &name, &version.to_string())?;
+    } else {
+        LinkedCopy {
+            namespace: namespace.clone(),
+            name: name.to_string(),
+            version: version.to_string(),
+            source: curr.display().to_string(),
+        }
+        .save()?;
+    }
+
+    if cmd.watch {
+        drop(lock);
+        watch(cmd, &curr, &destination, &extra).await?;
+    }
     Ok(true)
 }
+
+/// Picks the version of a development link given an alias but no version.
+///
+/// An alias keeps the version it has been linked under before, new aliases take
+/// the first `0.0.x` that is free in `package`.
+fn alias_version(
+    package: &Path,
+    namespace: &str,
+    name: &str,
+    alias: &str,
+) -> Result<PackageVersion> {
+    let links = DevLink::all(namespace, name)?;
+    if let Some(version) = links
+        .iter()
+        .filter(|link| link.alias.as_deref() == Some(alias))
+        .find_map(|link| PackageVersion::from_str(&link.version).ok())
+    {
+        return Ok(version);
+    }
+    let mut version = DEV_VERSION;
+    while package.join(version.to_string()).symlink_metadata().is_ok()
+        || links.iter().any(|link| link.version == version.to_string())
+    {
+        version.patch += 1;
+    }
+    Ok(version)
+}
+
+/// Builds a walker over the project files, honoring the ignore files selected in
+/// `cmd` and the excludes of the manifest.
+fn walker(cmd: &LinkArgs, curr: &Path, extra: &Extra) -> Result<WalkBuilder> {
+    // Use WalkBuilder to respect ignore files.
+    let mut wb: WalkBuilder = WalkBuilder::new(curr);
+    let mut overr: OverrideBuilder = OverrideBuilder::new(curr);
+
+    // Add excludes from the manifest to the override builder.
+    if let Some(excludes) = &extra.exclude {
+        for exclude in excludes.iter() {
+            overr.add(&format!("!{}", exclude))?;
+        }
+    }
+    wb.overrides(overr.build()?);
+
+    // Configure which ignore files to use.
+    wb.ignore(cmd.ignore)
+        .git_ignore(cmd.git_ignore)
+        .git_global(cmd.git_global_ignore)
+        .git_exclude(cmd.git_exclude);
+
+    // Add .typstignore if it exists and is enabled.
+    if cmd.typst_ignore && check_path_file(curr.join(".typstignore")) {
+        utpm_log!(trace, "Added .typstignore");
+        wb.add_custom_ignore_filename(".typstignore");
+    }
+    Ok(wb)
+}
+
+/// The files and directories to link, relative to the project.
+///
+/// Files are mapped to their modification time and size, directories to `None`.
+pub type Snapshot = BTreeMap<PathBuf, Option<(SystemTime, u64)>>;
+
+/// Lists the files and directories to link, relative to `curr`.
+pub fn snapshot(cmd: &LinkArgs, curr: &Path, extra: &Extra) -> Result<Snapshot> {
+    let mut files = BTreeMap::new();
+    for result in walker(cmd, curr, extra)?
+        .build()
+        .collect::<std::result::Result<Vec<_>, _>>()?
+    {
+        let Some(file_type) = result.file_type() else {
+            continue;
+        };
+        let relative = result.path().strip_prefix(curr).unwrap().to_path_buf();
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let stamp = if file_type.is_dir() {
+            None
+        } else {
+            let meta = result.metadata()?;
+            Some((meta.modified()?, meta.len()))
+        };
+        files.insert(relative, stamp);
+    }
+    Ok(files)
+}
+
+/// Keeps the copy at `destination` in sync with `curr` until interrupted.
+///
+/// The project is polled for changes: new and modified files are copied again,
+/// files deleted from the project are removed from the linked package.
+async fn watch(cmd: &LinkArgs, curr: &Path, destination: &Path, extra: &Extra) -> Result<()> {
+    utpm_log!(
+        info,
+        "Watching {} for changes, press Ctrl+C to stop",
+        curr.display()
+    );
+    let mut known = snapshot(cmd, curr, extra)?;
+    let stop = tokio::signal::ctrl_c();
+    tokio::pin!(stop);
+
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = tokio::time::sleep(WATCH_INTERVAL) => {},
+        }
+
+        let current = snapshot(cmd, curr, extra)?;
+        if current != known {
+            let _lock = StoreLock::acquire()?;
+            sync(curr, destination, &known, &current)?;
+            known = current;
+        }
+    }
+
+    utpm_log!(info, "Stopped watching {}", curr.display());
+    Ok(())
+}
+
+/// Brings the copy at `destination` up to date with `curr`, given the snapshot
+/// `known` of the last sync and the `current` one.
+///
+/// New and modified files are copied again, files deleted from the project are
+/// removed from the copy. Returns the number of changed and removed paths.
+pub fn sync(
+    curr: &Path,
+    destination: &Path,
+    known: &Snapshot,
+    current: &Snapshot,
+) -> Result<(usize, usize)> {
+    let changed = current
+        .iter()
+        .filter(|(relative, stamp)| known.get(*relative) != Some(*stamp))
+        .collect::<Vec<_>>();
+    let removed = known
+        .keys()
+        .filter(|relative| !current.contains_key(*relative))
+        .collect::<Vec<_>>();
+    if changed.is_empty() && removed.is_empty() {
+        return Ok((0, 0));
+    }
+
+    for (relative, stamp) in &changed {
+        let dest_path = destination.join(relative);
+        utpm_log!(info, "~ {}", relative.display());
+        if get_dry_run() {
+            continue;
+        }
+        if stamp.is_none() {
+            create_dir_all(&dest_path)?;
+        } else {
+            create_dir_all(dest_path.parent().unwrap())?;
+            copy(curr.join(relative), &dest_path)?;
+        }
+    }
+    for relative in &removed {
+        utpm_log!(info, "- {}", relative.display());
+        if !get_dry_run() {
+            remove_path(&destination.join(relative))?;
+        }
+    }
+    checksum::save(destination)?;
+    utpm_log!(
+        info,
+        format!("Synced {}", destination.display()),
+        "changed" => changed.len(),
+        "removed" => removed.len()
+    );
+    Ok((changed.len(), removed.len()))
+}
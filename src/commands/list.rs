@@ -1,238 +1,535 @@
+use clap::ValueEnum;
 use fmt_derive::{Debug, Display};
-use ptree::{TreeItem, print_tree};
+use globset::Glob;
+use itertools::Itertools;
+use ptree::{item::StringItem, print_tree};
 use serde::Serialize;
-use std::borrow::Cow;
 use std::fs;
 use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
 use tracing::instrument;
+use typst_syntax::package::PackageVersion;
 
 use crate::{
     utils::{
+        dir_stats, format_date, format_size,
         output::{OutputFormat, get_output_format},
-        paths::{package_cache_path, package_path},
+        paths::{check_path_dir, package_cache_path, package_path},
+        provenance::{DevLink, LinkedCopy, Provenance, VersionRecord},
         state::Result,
+        try_find,
     },
     utpm_log,
 };
 
-/// Represents a collection of namespaces at a specific path.
-#[derive(Serialize, Display, Debug, Clone)]
-#[display("{}", list_namespace.iter().map(|ns| ns.to_string()).collect::<Vec<_>>().join(""))]
-pub struct Data {
-    path: String,
-    list_namespace: Vec<Namespace>,
+/// Selects packages by namespace and name, e.g. `@preview`, `@local/mypkg` or `@preview/cet*`.
+///
+/// Both parts accept glob patterns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    namespace: Glob,
+    name: Option<Glob>,
 }
 
-impl Data {
-    /// Creates a new `Data` instance.
-    pub fn new(path: String) -> Self {
-        Self {
-            list_namespace: vec![],
-            path,
-        }
+impl Query {
+    /// Whether the query can match packages of this namespace.
+    pub fn matches_namespace(&self, namespace: &str) -> bool {
+        self.namespace.compile_matcher().is_match(namespace)
+    }
+
+    /// Whether the query matches this package.
+    pub fn matches(&self, namespace: &str, name: &str) -> bool {
+        self.matches_namespace(namespace)
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|glob| glob.compile_matcher().is_match(name))
     }
 }
 
-impl TreeItem for Data {
-    type Child = Namespace;
+impl FromStr for Query {
+    type Err = String;
 
-    fn write_self<W: std::io::Write>(
-        &self,
-        w: &mut W,
-        _style: &ptree::Style,
-    ) -> std::io::Result<()> {
-        write!(w, "{}", self.path)
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let Some(query) = s.strip_prefix('@') else {
+            return Err(format!(
+                "`{s}` should start with `@`, e.g. `@preview` or `@preview/cetz`"
+            ));
+        };
+        if query.contains(':') {
+            return Err(format!(
+                "`{s}` contains a version, only `@namespace` or `@namespace/name` are accepted"
+            ));
+        }
+        let (namespace, name) = match query.split_once('/') {
+            Some((namespace, name)) => (namespace, Some(name)),
+            None => (query, None),
+        };
+        if namespace.is_empty() || name.is_some_and(str::is_empty) {
+            return Err(format!("`{s}` is missing a namespace or a name"));
+        }
+        let glob = |pattern: &str| Glob::new(pattern).map_err(|err| err.to_string());
+        Ok(Self {
+            namespace: glob(namespace)?,
+            name: name.map(glob).transpose()?,
+        })
     }
+}
 
-    fn children(&self) -> Cow<'_, [Namespace]> {
-        Cow::Borrowed(&self.list_namespace)
-    }
+/// How to sort the listed packages.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum SortKey {
+    /// By namespace, name and version.
+    #[default]
+    Name,
+    /// Newest versions first.
+    Version,
+    /// Largest first.
+    Size,
+    /// Most recently modified first.
+    Mtime,
 }
 
-/// Represents a package namespace containing multiple packages.
+/// The packages found in both stores.
 #[derive(Serialize, Display, Debug, Clone)]
-#[display("\n* {}: \n{}", name, list_packages.iter().map(|ns| ns.to_string()).collect::<Vec<_>>().join("\n"))]
-pub struct Namespace {
-    name: String,
-    list_packages: Vec<Package>,
+#[display("{}", if packages.is_empty() { "No package found".to_string() } else { packages.iter().map(|p| p.to_string()).join("\n") })]
+pub struct Listing {
+    local: String,
+    cache: String,
+    packages: Vec<Entry>,
 }
 
-impl Namespace {
-    /// Creates a new `Namespace` instance.
-    pub fn new(name: String) -> Self {
-        Self {
-            list_packages: vec![],
-            name,
+impl Listing {
+    /// Groups the listed versions by namespace and package, keeping their order.
+    fn tree(&self) -> StringItem {
+        let mut namespaces: Vec<StringItem> = vec![];
+        for entry in &self.packages {
+            let namespace = match namespaces.iter().position(|ns| ns.text == entry.namespace) {
+                Some(i) => &mut namespaces[i],
+                None => {
+                    namespaces.push(item(entry.namespace.clone(), vec![]));
+                    namespaces.last_mut().unwrap()
+                },
+            };
+            let package = match namespace
+                .children
+                .iter()
+                .position(|pkg| pkg.text == entry.name)
+            {
+                Some(i) => &mut namespace.children[i],
+                None => {
+                    namespace.children.push(item(entry.name.clone(), vec![]));
+                    namespace.children.last_mut().unwrap()
+                },
+            };
+            package.children.push(item(
+                entry.version.describe(),
+                entry
+                    .version
+                    .details()
+                    .into_iter()
+                    .map(|line| item(line, vec![]))
+                    .collect(),
+            ));
         }
+        item(format!("{} + {}", self.local, self.cache), namespaces)
     }
 }
 
-impl TreeItem for Namespace {
-    type Child = Package;
+/// Builds a node of the tree view.
+fn item(text: String, children: Vec<StringItem>) -> StringItem {
+    StringItem { text, children }
+}
 
-    fn write_self<W: std::io::Write>(
-        &self,
-        w: &mut W,
-        _style: &ptree::Style,
-    ) -> std::io::Result<()> {
-        write!(w, "{}", self.name)
-    }
+/// A single version of a package, in one of the stores.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("@{namespace}/{name}:{version}")]
+pub struct Entry {
+    pub namespace: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub version: Version,
+}
 
-    fn children(&self) -> Cow<'_, [Package]> {
-        Cow::Borrowed(&self.list_packages)
-    }
+/// The package store a package directory lives in.
+#[derive(Serialize, Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    /// The local package directory, see `package_path()`.
+    #[display("local")]
+    Local,
+    /// The cache of downloaded packages, see `package_cache_path()`.
+    #[display("cache")]
+    Cache,
+}
+
+/// How a package version ended up in the store.
+#[derive(Serialize, Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A symlink to a project, made by `prj link --no-copy`.
+    #[display("symlink")]
+    Symlink,
+    /// A copy of a project, made by `prj link`.
+    #[display("copy")]
+    Copy,
+    /// A package installed by `pkg install`.
+    #[display("git")]
+    Git,
+    /// A package downloaded from the registry.
+    #[display("download")]
+    Download,
 }
 
-/// Represents a package with its available versions.
+/// Represents a single version of a package and where it comes from.
 #[derive(Serialize, Display, Debug, Clone)]
-#[display("* * {name}: {}", list_version.join(", "))]
-pub struct Package {
-    name: String,
-    list_version: Vec<String>,
+#[display("{}", std::iter::once(self.describe()).chain(self.details()).collect::<Vec<_>>().join("\n    "))]
+pub struct Version {
+    pub version: String,
+    pub store: Store,
+    pub kind: Kind,
+    /// Where the version comes from: the target of a symlink, the source of a git install
+    /// or the project of a development link.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Whether the target of a symlink is missing.
+    pub broken: bool,
+    /// Whether the version has been linked with `prj link --as-version` or `--alias`.
+    pub dev: bool,
+    /// The alias given with `prj link --alias`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Size on disk, in bytes.
+    pub size: u64,
+    /// Last modification, in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    /// Details of the manifest, only read with `--long`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+    /// Problems found while reading the manifest, only checked with `--long`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
 }
 
-impl Package {
-    /// Creates a new `Package` instance.
-    pub fn new(name: String) -> Self {
-        Self {
-            list_version: vec![],
-            name,
+/// Details read from the `typst.toml` of a package version.
+#[derive(Serialize, Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<String>,
+    pub entrypoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Version {
+    /// Reads the details of the version directory at `path`.
+    ///
+    /// With `long`, its manifest is parsed as well and compared to the directory.
+    pub fn read(
+        path: &Path,
+        store: Store,
+        namespace: &str,
+        name: &str,
+        long: bool,
+    ) -> Result<Self> {
+        let version = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let dev_link = DevLink::load(namespace, name, &version)?;
+        let linked = LinkedCopy::load(namespace, name, &version)?;
+        let is_symlink = path.symlink_metadata()?.file_type().is_symlink();
+        let broken = is_symlink && !path.exists();
+
+        let (kind, source) = if is_symlink {
+            let target = fs::read_link(path)?.display().to_string();
+            (Kind::Symlink, Some(target))
+        } else if let Some(record) =
+            Provenance::load(namespace, name)?.filter(|record| record.versions.contains(&version))
+        {
+            (Kind::Git, Some(record.source))
+        } else if store == Store::Cache && dev_link.is_none() && linked.is_none() {
+            (Kind::Download, None)
+        } else {
+            let source = linked
+                .map(|copy| copy.source)
+                .or_else(|| dev_link.as_ref().map(|link| link.source.clone()));
+            (Kind::Copy, source)
+        };
+
+        let (size, modified) = if broken { (0, None) } else { dir_stats(path)? };
+
+        let mut data = Self {
+            version,
+            store,
+            kind,
+            source,
+            broken,
+            dev: dev_link.is_some(),
+            alias: dev_link.and_then(|link| link.alias),
+            size,
+            modified: modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+            manifest: None,
+            problems: vec![],
+        };
+        if long && !broken {
+            data.read_manifest(path, name);
         }
+        Ok(data)
     }
-}
 
-impl TreeItem for Package {
-    type Child = Package; // Can be anything, since it has no children
+    /// Parses the manifest of the version and flags any disagreement with its path.
+    ///
+    /// Development links are expected to live under another version than the one
+    /// of their manifest, so only their name is checked.
+    fn read_manifest(&mut self, path: &Path, name: &str) {
+        let file = match try_find(path) {
+            Ok(file) => file,
+            Err(_) if !path.join("typst.toml").exists() => {
+                self.problems.push("missing manifest".into());
+                return;
+            },
+            Err(err) => {
+                self.problems.push(format!("invalid manifest: {err}"));
+                return;
+            },
+        };
+        let package = file.package;
+        if package.name != name {
+            self.problems
+                .push(format!("manifest name is {}", package.name));
+        }
+        if !self.dev && package.version.to_string() != self.version {
+            self.problems
+                .push(format!("manifest version is {}", package.version));
+        }
+        if !path.join(package.entrypoint.as_str()).is_file() {
+            self.problems
+                .push(format!("missing entrypoint {}", package.entrypoint));
+        }
+        self.manifest = Some(Manifest {
+            name: package.name.to_string(),
+            version: package.version.to_string(),
+            compiler: package.compiler.map(|bound| bound.to_string()),
+            entrypoint: package.entrypoint.to_string(),
+            description: package.description.map(|d| d.to_string()),
+        });
+    }
 
-    fn write_self<W: std::io::Write>(
-        &self,
-        w: &mut W,
-        _style: &ptree::Style,
-    ) -> std::io::Result<()> {
-        write!(w, "{}: {}", self.name, self.list_version.join(", "))
+    /// The version as a `PackageVersion`, if the directory name is a valid one.
+    pub fn parsed(&self) -> Option<PackageVersion> {
+        PackageVersion::from_str(&self.version).ok()
     }
 
-    fn children(&self) -> Cow<'_, [Self::Child]> {
-        Cow::Borrowed(&[]) // No children
+    /// Lines describing the manifest and its problems, shown under the version.
+    fn details(&self) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(manifest) = &self.manifest {
+            let mut line = format!(
+                "{}:{}, entrypoint {}",
+                manifest.name, manifest.version, manifest.entrypoint
+            );
+            if let Some(compiler) = &manifest.compiler {
+                line.push_str(&format!(", typst >= {compiler}"));
+            }
+            lines.push(line);
+            if let Some(description) = &manifest.description {
+                lines.push(description.clone());
+            }
+        }
+        lines.extend(self.problems.iter().map(|problem| format!("! {problem}")));
+        lines
+    }
+
+    /// Describes the version on a single line, e.g. `0.0.1 [local, copy, dev, alias wip] 12.3 KiB, 2025-01-31`.
+    fn describe(&self) -> String {
+        let mut tags = vec![self.store.to_string(), self.kind.to_string()];
+        if self.dev {
+            tags.push("dev".into());
+        }
+        if let Some(alias) = &self.alias {
+            tags.push(format!("alias {alias}"));
+        }
+        if self.broken {
+            tags.push("broken".into());
+        }
+        if !self.problems.is_empty() {
+            tags.push("invalid".into());
+        }
+        let mut line = format!("{} [{}]", self.version, tags.join(", "));
+        if let Some(source) = &self.source {
+            line.push_str(&format!(" <- {source}"));
+        }
+        line.push_str(&format!(" {}", format_size(self.size)));
+        if let Some(modified) = self.modified {
+            line.push_str(&format!(
+                ", {}",
+                format_date(UNIX_EPOCH + Duration::from_secs(modified))
+            ));
+        }
+        line
     }
 }
 
 use super::ListTreeArgs;
 
-/// Lists packages in local storage.
+/// Lists packages from the local package directory and the download cache.
 ///
-/// Can display as a simple list or as a tree structure, depending on the
-/// command-line arguments and output format.
+/// Packages can be selected with queries, then sorted and limited. They are
+/// displayed as a list or as a tree, depending on the command-line arguments
+/// and output format.
 #[instrument(skip(cmd))]
 pub async fn run(cmd: &ListTreeArgs) -> Result<bool> {
-    // If tree view is requested with text output, use the tree-specific function.
-    if cmd.tree && get_output_format() == OutputFormat::Text {
-        return run_tree(cmd);
-    }
-    let typ = package_path()?;
-    // If `--all` is specified, list packages from both data and cache directories.
+    utpm_log!(trace, "executing list command");
     if cmd.all {
-        let preview = package_cache_path()?;
-        let data1 = read(&typ)?;
-        let data2 = read(&preview)?;
-        utpm_log!(data1);
-        utpm_log!(data2);
-        return Ok(true);
-    }
-
-    // If specific packages/namespaces are included, list only those.
-    if let Some(list) = &cmd.include {
-        let preview = package_cache_path()?;
-        for e in list {
-            if e == "preview" {
-                let data = read(&preview)?;
-                utpm_log!(data);
-                return Ok(true);
-            }
-            let pkg = package_read(typ.join("local").join(e), e.to_string());
-            match pkg {
-                Err(_) => {
-                    utpm_log!(namespace_read(typ.join(e), e.to_string())?);
-                },
-                Ok(data) => {
-                    utpm_log!(data)
-                },
-            };
-        }
-        Ok(true)
+        utpm_log!(
+            warn,
+            "--all is deprecated and does nothing, every package is listed by default"
+        );
+    }
+    let local = package_path()?;
+    let cache = package_cache_path()?;
+
+    let mut packages = read(&local, Store::Local, &cmd.queries, cmd.long)?;
+    packages.extend(read(&cache, Store::Cache, &cmd.queries, cmd.long)?);
+    if cmd.latest_only {
+        packages = latest_only(packages);
+    }
+    sort(&mut packages, cmd.sort);
+    if let Some(limit) = cmd.limit {
+        packages.truncate(limit);
+    }
+
+    let listing = Listing {
+        local: local.display().to_string(),
+        cache: cache.display().to_string(),
+        packages,
+    };
+    // The tree view is only available with text output.
+    if cmd.tree && get_output_format() == OutputFormat::Text {
+        print_tree(&listing.tree())?;
     } else {
-        // By default, list packages from the data directory.
-        let data = read(&typ)?;
-        utpm_log!(data);
-        return Ok(true);
+        utpm_log!(info, listing);
     }
+    Ok(true)
 }
 
-/// Reads all namespaces and packages from a given directory path.
-pub fn read(typ: impl AsRef<Path>) -> Result<Data> {
-    let typ_path = typ.as_ref();
-    let dirs = fs::read_dir(typ_path)?;
-    let mut data = Data::new(typ_path.display().to_string());
+/// Sorts the versions, see [`SortKey`].
+pub fn sort(packages: &mut [Entry], key: SortKey) {
+    let name = |e: &Entry| (e.namespace.clone(), e.name.clone(), e.version.parsed());
+    match key {
+        SortKey::Name => packages.sort_by_key(name),
+        SortKey::Version => packages.sort_by(|a, b| {
+            b.version
+                .parsed()
+                .cmp(&a.version.parsed())
+                .then(name(a).cmp(&name(b)))
+        }),
+        SortKey::Size => packages.sort_by(|a, b| {
+            b.version
+                .size
+                .cmp(&a.version.size)
+                .then(name(a).cmp(&name(b)))
+        }),
+        SortKey::Mtime => packages.sort_by(|a, b| {
+            b.version
+                .modified
+                .cmp(&a.version.modified)
+                .then(name(a).cmp(&name(b)))
+        }),
+    }
+}
 
-    for dir_res in dirs {
-        let dir = dir_res?;
-        let nms = namespace_read(dir.path(), dir.file_name().to_string_lossy().to_string())?;
-        data.list_namespace.push(nms);
+/// Keeps the highest version of each package.
+///
+/// If both stores hold it, the local one wins, like in Typst.
+pub fn latest_only(packages: Vec<Entry>) -> Vec<Entry> {
+    packages
+        .into_iter()
+        .into_group_map_by(|e| (e.namespace.clone(), e.name.clone()))
+        .into_values()
+        .filter_map(|versions| {
+            versions
+                .into_iter()
+                .max_by_key(|e| (e.version.parsed(), e.version.store == Store::Local))
+        })
+        .collect()
+}
+
+/// Reads the versions of every package in a store that match one of `queries`.
+///
+/// Everything is read if there is no query.
+pub fn read(
+    typ: impl AsRef<Path>,
+    store: Store,
+    queries: &[Query],
+    long: bool,
+) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for namespace in sub_dirs(typ.as_ref())? {
+        if !queries.is_empty() && !queries.iter().any(|q| q.matches_namespace(&namespace)) {
+            continue;
+        }
+        for name in sub_dirs(&typ.as_ref().join(&namespace))? {
+            if !queries.is_empty() && !queries.iter().any(|q| q.matches(&namespace, &name)) {
+                continue;
+            }
+            entries.extend(package_read(
+                typ.as_ref().join(&namespace).join(&name),
+                store,
+                &namespace,
+                name,
+                long,
+            )?);
+        }
     }
-    Ok(data)
+    Ok(entries)
 }
 
 /// Reads all versions of a specific package.
-pub fn package_read(typ: impl AsRef<Path>, name: String) -> Result<Package> {
-    let mut pkg = Package::new(name);
+pub fn package_read(
+    typ: impl AsRef<Path>,
+    store: Store,
+    namespace: &str,
+    name: String,
+    long: bool,
+) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
     for dir_res in fs::read_dir(typ)? {
         let dir: fs::DirEntry = dir_res?;
-        pkg.list_version
-            .push(dir.file_name().to_string_lossy().to_string());
+        // Skip directories staged by an ongoing (or interrupted) link.
+        if dir.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        entries.push(Entry {
+            namespace: namespace.to_string(),
+            name: name.clone(),
+            version: Version::read(&dir.path(), store, namespace, &name, long)?,
+        });
     }
-    pkg.list_version.sort();
-    Ok(pkg)
+    sort(&mut entries, SortKey::Name);
+    Ok(entries)
 }
 
-/// Reads all packages within a specific namespace.
-pub fn namespace_read(typ: impl AsRef<Path>, name: String) -> Result<Namespace> {
-    let mut nms = Namespace::new(name);
-    for dir_res in fs::read_dir(typ)? {
-        let dir = dir_res?;
-        let pkg = package_read(dir.path(), dir.file_name().to_string_lossy().to_string())?;
-        nms.list_packages.push(pkg);
+/// Names of the visible directories in `path`, or nothing if it doesn't exist.
+fn sub_dirs(path: &Path) -> Result<Vec<String>> {
+    if !check_path_dir(path) {
+        return Ok(vec![]);
     }
-    Ok(nms)
-}
-
-/// Displays the packages in a tree format.
-#[instrument(skip(cmd))]
-pub fn run_tree(cmd: &ListTreeArgs) -> Result<bool> {
-    utpm_log!(trace, "executing list command with tree format");
-    let packages = package_path()?;
-    if cmd.all {
-        let cache = package_cache_path()?;
-        let packages_data = read(&packages)?;
-        let cache_data = read(&cache)?;
-        print_tree(&packages_data)?;
-        print_tree(&cache_data)?;
-    } else if let Some(list) = &cmd.include {
-        let preview = package_cache_path()?;
-        for e in list {
-            if e == "preview" {
-                let data = read(&preview)?;
-                print_tree(&data)?;
-                return Ok(true);
-            }
-            let pkg = package_read(packages.join("local").join(e), e.to_string());
-            match pkg {
-                Err(_) => print_tree(&namespace_read(packages.join(e), e.to_string())?),
-                Ok(data) => print_tree(&data),
-            }?;
+    let mut names = vec![];
+    for dir_res in fs::read_dir(path)? {
+        let dir = dir_res?;
+        let name = dir.file_name().to_string_lossy().to_string();
+        if dir.path().is_dir() && !name.starts_with('.') {
+            names.push(name);
         }
-    } else {
-        let data = read(&packages)?;
-        print_tree(&data)?;
     }
-    Ok(true)
+    Ok(names)
 }
@@ -1,4 +1,7 @@
+use std::fs::read_to_string;
+
 use serde::Serialize;
+use toml::{Table, Value};
 use tracing::instrument;
 
 use crate::{
@@ -6,9 +9,9 @@ use crate::{
         output::{OutputFormat, get_output_format},
         paths::get_current_dir,
         state::Result,
-        try_find,
+        try_find, try_find_path,
     },
-    utpm_log,
+    utpm_bail, utpm_log,
 };
 
 use super::MetadataArgs;
@@ -113,33 +116,15 @@ pub async fn run(cmd: &MetadataArgs) -> Result<bool> {
     };
 
     // Handle specific field request
-    if let Some(field) = &cmd.field {
-        let value = match field.as_str() {
-            "name" => Some(metadata.name.clone()),
-            "version" => Some(metadata.version.clone()),
-            "entrypoint" => Some(metadata.entrypoint.clone()),
-            "authors" => Some(metadata.authors.join(", ")),
-            "license" => metadata.license.clone(),
-            "description" => metadata.description.clone(),
-            "repository" => metadata.repository.clone(),
-            "homepage" => metadata.homepage.clone(),
-            "keywords" => metadata.keywords.as_ref().map(|k| k.join(", ")),
-            "categories" => metadata.categories.as_ref().map(|c| c.join(", ")),
-            "disciplines" => metadata.disciplines.as_ref().map(|d| d.join(", ")),
-            "compiler" => metadata.compiler.clone(),
-            "exclude" => metadata.exclude.as_ref().map(|e| e.join(", ")),
-            _ => {
-                utpm_log!(error, "Unknown field: {}", field);
-                return Ok(false);
-            },
-        };
-
-        if let Some(val) = value {
-            // For single field extraction, output plain text for all formats
-            // TOML/YAML/HJSON don't support serializing plain strings without a key
-            println!("{}", val);
-        } else {
-            utpm_log!(info, "Field '{}' is not set", field);
+    if cmd.field.is_some() || cmd.format.is_some() {
+        let manifest: Table = toml::from_str(&read_to_string(try_find_path(&curr)?)?)?;
+        // For single field extraction, output plain text for all formats
+        // TOML/YAML/HJSON don't support serializing plain strings without a key
+        if let Some(field) = &cmd.field {
+            println!("{}", render(lookup(&manifest, field)?));
+        }
+        if let Some(format) = &cmd.format {
+            println!("{}", format_fields(&manifest, format)?);
         }
     } else {
         // Output all metadata
@@ -198,3 +183,130 @@ pub async fn run(cmd: &MetadataArgs) -> Result<bool> {
 
     Ok(true)
 }
+
+/// Finds the value at `path` in a manifest.
+///
+/// Paths are dotted keys with optional array indices, like `tool.utpm.exclude` or
+/// `package.authors[0]`. A path whose first key isn't a table of the manifest is
+/// looked up in `[package]`, then in `[tool.utpm]`, so `version` is the same as
+/// `package.version`.
+pub fn lookup<'a>(manifest: &'a Table, path: &str) -> Result<&'a Value> {
+    let first = path.split(['.', '[']).next().unwrap_or_default();
+    if !manifest.contains_key(first) {
+        for section in ["package", "tool.utpm"] {
+            if lookup_path(manifest, &format!("{section}.{first}")).is_ok() {
+                return lookup_path(manifest, &format!("{section}.{path}"));
+            }
+        }
+    }
+    lookup_path(manifest, path)
+}
+
+fn lookup_path<'a>(manifest: &'a Table, path: &str) -> Result<&'a Value> {
+    let fail =
+        |reason: String| -> Result<&'a Value> { utpm_bail!(MetadataField, path.into(), reason) };
+
+    let mut table = manifest;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let (key, indices) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+        if key.is_empty() {
+            return fail("a key is empty".into());
+        }
+        let Some(mut value) = table.get(key) else {
+            return fail(format!("`{key}` is not set"));
+        };
+        if !indices.is_empty() {
+            let Some(indices) = indices.strip_prefix('[').and_then(|i| i.strip_suffix(']')) else {
+                return fail(format!("`{segment}` has an invalid index"));
+            };
+            for index in indices.split("][") {
+                let Ok(index) = index.parse::<usize>() else {
+                    return fail(format!("`{segment}` has an invalid index"));
+                };
+                let Value::Array(items) = value else {
+                    return fail(format!("`{key}` is not an array"));
+                };
+                let Some(item) = items.get(index) else {
+                    return fail(format!(
+                        "`{key}` has {} item(s), no index {index}",
+                        items.len()
+                    ));
+                };
+                value = item;
+            }
+        }
+        if segments.peek().is_none() {
+            return Ok(value);
+        }
+        let Value::Table(inner) = value else {
+            return fail(format!("`{segment}` is not a table"));
+        };
+        table = inner;
+    }
+    unreachable!("splitting a path yields at least one segment")
+}
+
+/// Formats a manifest value for scripts.
+///
+/// Strings are printed without quotes and arrays of plain values are joined with
+/// commas. Tables are printed as TOML.
+pub fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items)
+            if items
+                .iter()
+                .all(|item| !item.is_table() && !item.is_array()) =>
+        {
+            items.iter().map(render).collect::<Vec<_>>().join(", ")
+        },
+        Value::Table(table) => toml::to_string(table)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Replaces every `{path}` of `template` with the rendered value at that path.
+///
+/// `{{` and `}}` stand for literal braces.
+pub fn format_fields(manifest: &Table, template: &str) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            },
+            '{' => {
+                let mut path = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => path.push(c),
+                        None => utpm_bail!(
+                            MetadataField,
+                            template.into(),
+                            "unmatched `{`, use `{{` for a literal brace".into()
+                        ),
+                    }
+                }
+                out.push_str(&render(lookup(manifest, path.trim())?));
+            },
+            '}' => utpm_bail!(
+                MetadataField,
+                template.into(),
+                "unmatched `}`, use `}}` for a literal brace".into()
+            ),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fmt_derive::{Debug, Display};
+use inquire::Confirm;
+use itertools::Itertools;
+use serde::Serialize;
+use tracing::instrument;
+use typst_syntax::package::PackageSpec;
+
+use crate::{
+    path,
+    utils::{
+        checksum,
+        dryrun::get_dry_run,
+        format_size,
+        imports::scan_imports,
+        paths::{get_current_dir, package_cache_path, package_path},
+        state::Result,
+        store::{StoreLock, remove_path},
+    },
+    utpm_bail, utpm_log,
+};
+
+use super::{
+    PruneArgs,
+    fetch::{Status, resolve},
+    list::{Entry, Kind, Store, read},
+};
+
+/// A cached version selected for removal.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("- @{namespace}/{name}:{version} ({})", format_size(*size))]
+pub struct Pruned {
+    namespace: String,
+    name: String,
+    version: String,
+    size: u64,
+}
+
+/// What `pkg prune` removed, or would remove in dry-run mode.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{}{} version(s) removed, {} kept, {} reclaimed", removed.iter().map(|p| format!("{p}\n")).join(""), removed.len(), kept, format_size(*reclaimed))]
+pub struct Report {
+    removed: Vec<Pruned>,
+    kept: usize,
+    reclaimed: u64,
+}
+
+/// Removes the downloaded packages that no project uses anymore.
+///
+/// Imports are collected from the given project directories, Typst files or
+/// lockfiles, then from the packages they import (see [`used_packages`]). Every
+/// downloaded version in the cache that is not imported is
+/// removed, unless it is one of the latest versions kept with `--keep-latest` or
+/// more recent than `--older-than`. Linked and installed packages are never removed.
+/// Nothing is pruned if a path doesn't exist or if no import is found at all, so a
+/// mistake can't empty the whole cache.
+///
+/// Prompts for confirmation unless `--yes` flag is used.
+#[instrument(skip(cmd))]
+pub async fn run(cmd: &PruneArgs) -> Result<bool> {
+    utpm_log!(trace, "executing prune command");
+    let roots = if cmd.paths.is_empty() {
+        vec![get_current_dir()?]
+    } else {
+        cmd.paths.clone()
+    };
+
+    let mut imports = HashSet::new();
+    for root in &roots {
+        // A mistyped path would make every cached version look unused.
+        if !root.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} does not exist", root.display()),
+            )
+            .into());
+        }
+        imports.extend(scan_imports(root)?);
+    }
+    utpm_log!(info, "Found {} imported package(s)", imports.len());
+    if imports.is_empty() {
+        utpm_bail!(
+            NoImports,
+            roots.iter().map(|root| root.display()).join(", ")
+        );
+    }
+
+    let cache = package_cache_path()?;
+    let imports = used_packages(imports, &package_path()?, &cache)?;
+    let entries = read(&cache, Store::Cache, &[], false)?;
+    // An age reaching before the epoch keeps every version.
+    let cutoff = cmd.older_than.map(|age| {
+        SystemTime::now()
+            .checked_sub(age)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs())
+    });
+    let (removed, kept) = select(entries, &imports, cmd.keep_latest, cutoff);
+
+    let report = Report {
+        reclaimed: removed.iter().map(|p| p.size).sum(),
+        kept,
+        removed,
+    };
+    if report.removed.is_empty() {
+        utpm_log!(info, "Nothing to prune");
+        return Ok(true);
+    }
+
+    // Confirm with the user before deleting, unless `--yes` is provided.
+    if !cmd.yes {
+        let confirmed = Confirm::new("This is irreversible. Are you sure to delete this?")
+            .with_help_message(
+                format!(
+                    "You want to delete {} version(s) ({})",
+                    report.removed.len(),
+                    format_size(report.reclaimed)
+                )
+                .as_str(),
+            )
+            .prompt();
+        if !matches!(confirmed, Ok(true)) {
+            return Ok(false);
+        }
+    }
+
+    if !get_dry_run() {
+        let _lock = StoreLock::acquire()?;
+        for pruned in &report.removed {
+            let package: PathBuf = path!(&cache, &pruned.namespace, &pruned.name);
+            let version = package.join(&pruned.version);
+            remove_path(&version)?;
+            checksum::remove(&version)?;
+            // Don't leave empty package directories behind.
+            if package.read_dir()?.next().is_none() {
+                remove_path(&package)?;
+            }
+        }
+    }
+    utpm_log!(info, report);
+    Ok(true)
+}
+
+/// Returns the packages needed by `imports`, directly or through other packages.
+///
+/// The imports of the packages found in `local` or `cache` are followed like
+/// `prj fetch` does, so the dependencies of a kept package are kept too.
+pub fn used_packages(
+    imports: HashSet<PackageSpec>,
+    local: &Path,
+    cache: &Path,
+) -> Result<HashSet<PackageSpec>> {
+    // Nothing is downloaded while pruning.
+    let packages = resolve(imports, local, cache, |_, _| Ok(Status::Missing))?;
+    Ok(packages.into_iter().map(|package| package.spec).collect())
+}
+
+/// Splits the downloaded versions into the ones to remove and the number of kept ones.
+///
+/// `cutoff` is a time in seconds since the Unix epoch: versions modified after it
+/// are kept.
+pub fn select(
+    entries: Vec<Entry>,
+    imports: &HashSet<PackageSpec>,
+    keep_latest: usize,
+    cutoff: Option<u64>,
+) -> (Vec<Pruned>, usize) {
+    let mut removed = vec![];
+    let mut kept = 0;
+    let packages = entries
+        .into_iter()
+        .filter(|entry| entry.version.kind == Kind::Download)
+        .into_group_map_by(|entry| (entry.namespace.clone(), entry.name.clone()));
+
+    for ((namespace, name), mut versions) in packages.into_iter().sorted_by(|a, b| a.0.cmp(&b.0)) {
+        // Newest first, so that the latest ones are at the front.
+        versions.sort_by_key(|entry| std::cmp::Reverse(entry.version.parsed()));
+        for (i, entry) in versions.into_iter().enumerate() {
+            let imported = imports.iter().any(|spec| {
+                spec.namespace == namespace
+                    && spec.name == name
+                    && spec.version.to_string() == entry.version.version
+            });
+            let recent = cutoff.is_some_and(|cutoff| {
+                entry
+                    .version
+                    .modified
+                    .is_none_or(|modified| modified > cutoff)
+            });
+            if imported || i < keep_latest || recent {
+                kept += 1;
+            } else {
+                removed.push(Pruned {
+                    namespace: namespace.clone(),
+                    name: name.clone(),
+                    version: entry.version.version,
+                    size: entry.version.size,
+                });
+            }
+        }
+    }
+    (removed, kept)
+}
+
+/// Parses an age such as `30d`, `12h`, `2w`, `45m` or `90s`.
+pub fn parse_age(s: &str) -> std::result::Result<Duration, String> {
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("`{s}` should be a number followed by s, m, h, d or w"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" | "" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit `{unit}`, use s, m, h, d or w")),
+    };
+    let seconds = number
+        .checked_mul(seconds)
+        .ok_or_else(|| format!("`{s}` is too long"))?;
+    Ok(Duration::from_secs(seconds))
+}
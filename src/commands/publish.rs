@@ -1,7 +1,7 @@
-use crate::utils::git::{add_git, clone_git, commit_git, exist_git, project, pull_git, push_git};
+use crate::utils::git::{self, exist_git};
 use crate::utils::specs::Extra;
 use crate::utils::state::Result;
-use crate::utils::{regex_package, try_find};
+use crate::utils::{regex_package, try_find, universe};
 use crate::utpm_log;
 use std::env;
 use std::fs::{copy, create_dir_all};
@@ -39,6 +39,15 @@ pub async fn run(cmd: &PublishArgs) -> Result<bool> {
     let config: PackageManifest = try_find(get_current_dir()?)?;
     utpm_log!(info, "Manifest load");
 
+    // Typst Universe rejects unknown categories and disciplines.
+    let mut problems = universe::lint(&config.package).into_iter();
+    if let Some(problem) = problems.next() {
+        for other in problems {
+            utpm_log!(error, "{}", other);
+        }
+        return Err(problem);
+    }
+
     let path_curr: &PathBuf = if let Some(path) = &cmd.path {
         path
     } else {
@@ -109,15 +118,11 @@ pub async fn run(cmd: &PublishArgs) -> Result<bool> {
         };
     }
 
-    project().lock().unwrap().0 = packages_path.clone();
-
     // --- File Preparation ---
     // Download or update the typst/packages repository.
-
-    match pull_git() {
-        Ok(_) => Ok(true),
-        Err(_) => clone_git(&packages_path.to_string_lossy(), fork.as_str()),
-    }?;
+    if git::open(&packages_path).pull("origin", "main").is_err() {
+        git::open(get_current_dir()?).clone(&fork, &packages_path)?;
+    }
     utpm_log!(
         info,
         "Path to the new package {}",
@@ -219,11 +224,10 @@ pub async fn run(cmd: &PublishArgs) -> Result<bool> {
         .clone()
         .unwrap_or(format!("{} using utpm", &name_replaced));
 
-    project().lock().unwrap().0 = new_package_path;
-
-    add_git(".")?;
-    commit_git(&msg)?;
-    push_git()?;
+    let git = git::open(new_package_path);
+    git.add(".")?;
+    git.commit(&msg)?;
+    git.push("origin", "main")?;
     utpm_log!(info, "Ended push");
 
     // --- Pull Request ---
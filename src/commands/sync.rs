@@ -7,14 +7,14 @@ use tracing::instrument;
 use std::result::Result as R;
 
 use crate::{
-    commands::get::get_packages_name_version,
-    path,
+    commands::get::latest_version,
     utils::{
         dryrun::get_dry_run,
-        paths::{get_current_dir, package_path},
-        state::{Result, UtpmError},
+        package_ref::{PackageRef, VersionReq},
+        paths::get_current_dir,
+        state::Result,
     },
-    utpm_bail, utpm_log,
+    utpm_log,
 };
 
 use super::SyncArgs;
@@ -59,10 +59,7 @@ async fn default_run(cmd: bool) -> Result<bool> {
 // TODO: Comments using utpm_log
 async fn file_run(path: impl AsRef<Path>, comment_only: bool) -> Result<bool> {
     let path = path.as_ref();
-    let re = Regex::new(
-        r#"\#import \"@([a-zA-Z]+)\/([a-zA-Z]+(?:\-[a-zA-Z]+)?)\:(\d+)\.(\d+)\.(\d+)\""#,
-    )
-    .unwrap();
+    let re = Regex::new(r#"\#import \"(@[^"]+)\""#).unwrap();
     let content_bytes = match std::fs::read(path) {
         Ok(bytes) => Ok(bytes),
         Err(e) => {
@@ -85,59 +82,37 @@ async fn file_run(path: impl AsRef<Path>, comment_only: bool) -> Result<bool> {
 
     // Matching regex
     let nws = string.clone();
-    let res = re.find_iter(nws.as_str());
-    let res2 = re.captures_iter(nws.as_str());
     utpm_log!(info, "Found imports");
-    for e in res.zip(res2) {
+    for cap in re.captures_iter(nws.as_str()) {
+        let (found, [spec]) = cap.extract();
+        // Only imports of an exact package version can be synced.
+        let Ok(PackageRef {
+            namespace: Some(namespace),
+            name: Some(package),
+            version: VersionReq::Exact(current),
+        }) = spec.parse::<PackageRef>()
+        else {
+            utpm_log!(debug, "Skipping import {}", spec);
+            continue;
+        };
         modified = true;
         // Set positions to rewrite the version
-        let start = (e.0.start() as isize + offset) as usize;
-        let end = (e.0.end() as isize + offset) as usize;
+        let start = (cap.get(0).unwrap().start() as isize + offset) as usize;
+        let end = start + found.len();
         let range = start..end;
         utpm_log!(trace, "Range: {:?}", range);
-
-        // Extract infos from the import
-        let (_, [namespace, package, major, minor, patch]) = e.1.extract();
-        utpm_log!(
-            trace,
-            "Last import: @{namespace}/{package}:{major}.{minor}.{}",
-            patch
-        );
-        let version = if namespace == "preview" {
-            let pkgs = get_packages_name_version().await?;
-            if let Some(pkg) = pkgs.get(package) {
-                Ok::<std::string::String, UtpmError>(pkg.version.clone())
-            } else {
-                utpm_bail!(PackageNotExist);
-            }
-        } else {
-            let r = std::fs::read_dir(path!(package_path()?, namespace, package))?;
-            let mut list_dir = r
-                .into_iter()
-                .filter_map(|a| a.ok())
-                .filter_map(|a| a.file_name().into_string().ok())
-                .collect::<Vec<_>>();
-            list_dir.sort();
-            let var = match list_dir.last() {
-                Some(e) => Ok::<std::string::String, UtpmError>(e.clone()),
-                None => utpm_bail!(PackageNotExist),
-            }?;
-            Ok(var)
-        }?;
+        utpm_log!(trace, "Last import: {}", spec);
+        let version = latest_version(&namespace, &package, &VersionReq::Any).await?;
 
         // Replace the import by the new
 
         let new_import: String = format!(
             "#import \"@{namespace}/{package}:{}\" {}",
+            if comment_only { current } else { version },
             if comment_only {
-                format!("{major}.{minor}.{patch}")
-            } else {
-                version.clone()
-            },
-            if comment_only {
-                format!("/* New version available: {} */", version)
+                format!("/* New version available: {version} */")
             } else {
-                format!("/* From {major}.{minor}.{patch} */")
+                format!("/* From {current} */")
             }
         );
         utpm_log!(info, new_import);
@@ -1,15 +1,18 @@
 use inquire::Confirm;
-use regex::Regex;
 use std::fs;
+use std::path::PathBuf;
 use tracing::instrument;
 
 use crate::{
     path,
     utils::{
+        checksum,
         dryrun::get_dry_run,
+        package_ref::{PackageRef, VersionReq},
         paths::{self, check_path_dir},
-        regex_package,
+        provenance::{Provenance, forget_links},
         state::Result,
+        store::StoreLock,
     },
     utpm_bail, utpm_log,
 };
@@ -31,6 +34,17 @@ fn package_path(namespace: &str) -> Result<String> {
     }
 }
 
+/// Forgets the link and install records of the removed packages.
+fn forget(pkg: &PackageRef) -> Result<()> {
+    let namespace = pkg.namespace.as_deref().unwrap_or_default();
+    let version = match &pkg.version {
+        VersionReq::Exact(version) => Some(version.to_string()),
+        _ => None,
+    };
+    forget_links(namespace, pkg.name.as_deref(), version.as_deref())?;
+    Provenance::forget(namespace, pkg.name.as_deref(), version.as_deref())
+}
+
 /// Unlinks (removes) a package from local storage.
 ///
 /// Supports removing:
@@ -44,29 +58,22 @@ pub async fn run(cmd: &UnlinkArgs) -> Result<bool> {
     utpm_log!(trace, "executing unlink command");
     let packages = &cmd.package;
 
-    // Use regex to parse the package string, which can be a full package spec,
-    // a package name, or just a namespace.
-    let re_all = regex_package();
-    let re_name = Regex::new(r"^@([a-zA-Z]+)\/([a-zA-Z]+(?:\-[a-zA-Z]+)?)$").unwrap();
-    let re_namespace = Regex::new(r"^@([a-zA-Z]+)$").unwrap();
-
-    let path = if let Some(cap) = re_all.captures(packages.as_str()) {
-        let (_, [namespace, package, major, minor, patch]) = cap.extract();
-        path!(
-            package_path(namespace)?,
-            namespace,
-            package,
-            format!("{major}.{minor}.{patch}")
-        )
-    } else if let Some(cap) = re_name.captures(packages.as_str()) {
-        let (_, [namespace, package]) = cap.extract();
-        path!(package_path(namespace)?, namespace, package)
-    } else if let Some(cap) = re_namespace.captures(packages.as_str()) {
-        let (_, [namespace]) = cap.extract();
-        path!(package_path(namespace)?, namespace)
-    } else {
-        utpm_bail!(PackageNotValid);
+    // The package can be a full package spec, a package name, or just a namespace.
+    let pkg: PackageRef = packages.parse()?;
+    let Some(namespace) = &pkg.namespace else {
+        return Err(pkg.invalid("the namespace is required, e.g. @local/example"));
     };
+    let mut path: PathBuf = path!(package_path(namespace)?, namespace.as_str());
+    if let Some(name) = &pkg.name {
+        path.push(name.as_str());
+    }
+    match pkg.version {
+        VersionReq::Any => {},
+        VersionReq::Exact(version) => path.push(version.to_string()),
+        VersionReq::Range(_) => {
+            return Err(pkg.invalid("unlink needs an exact version such as 1.0.0"));
+        },
+    }
 
     // Check if the package directory exists.
     if !check_path_dir(&path) {
@@ -82,7 +89,10 @@ pub async fn run(cmd: &UnlinkArgs) -> Result<bool> {
             Ok(_) => {
                 utpm_log!(info, "Deleting {}", path.display());
                 if !get_dry_run() {
-                    fs::remove_dir_all(path)?;
+                    let _lock = StoreLock::acquire()?;
+                    fs::remove_dir_all(&path)?;
+                    checksum::remove(&path)?;
+                    forget(&pkg)?;
                 }
                 Ok(true)
             },
@@ -91,7 +101,10 @@ pub async fn run(cmd: &UnlinkArgs) -> Result<bool> {
     } else {
         utpm_log!(info, "Deleting {}", path.display());
         if !get_dry_run() {
-            fs::remove_dir_all(path)?;
+            let _lock = StoreLock::acquire()?;
+            fs::remove_dir_all(&path)?;
+            checksum::remove(&path)?;
+            forget(&pkg)?;
         }
         Ok(true)
     }
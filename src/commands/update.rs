@@ -0,0 +1,113 @@
+use tracing::instrument;
+
+use crate::{
+    path,
+    utils::{
+        git::exist_git,
+        package_ref::{PackageRef, VersionReq},
+        paths::{check_path_dir, package_cache_path, package_path},
+        provenance::Provenance,
+        state::Result,
+        store::temp_dir,
+        try_find,
+    },
+    utpm_bail, utpm_log,
+};
+
+use super::{
+    UpdateArgs,
+    install::{fetch_source, is_remote, link_fetched},
+};
+
+/// Updates packages installed with `pkg install`.
+///
+/// Fetches the recorded source of each package again and links the version found
+/// there next to the versions already installed.
+#[instrument(skip(cmd))]
+pub async fn run(cmd: &UpdateArgs) -> Result<bool> {
+    utpm_log!(trace, "executing update command");
+    let filter = cmd
+        .name
+        .as_deref()
+        .map(str::parse::<PackageRef>)
+        .transpose()?;
+    if let Some(pkg) = &filter {
+        pkg.require_name()?;
+        if pkg.version != VersionReq::Any {
+            return Err(pkg.invalid("update fetches the latest version, remove the version"));
+        }
+    }
+    let records = Provenance::all()?
+        .into_iter()
+        .filter(|record| {
+            filter.as_ref().is_none_or(|pkg| {
+                pkg.name.as_deref() == Some(record.name.as_str())
+                    && pkg
+                        .namespace
+                        .as_ref()
+                        .is_none_or(|ns| ns == &record.namespace)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if records.is_empty() {
+        if cmd.name.is_some() {
+            utpm_bail!(PackageNotExist);
+        }
+        utpm_log!(info, "No installed package to update");
+        return Ok(true);
+    }
+
+    // A package that can't be updated doesn't stop the others.
+    let mut failed = vec![];
+    for mut record in records {
+        if let Err(err) = update(&mut record).await {
+            utpm_log!(warn, "Can't update {}: {}", record.name, err);
+            failed.push(format!("@{}/{}", record.namespace, record.name));
+        }
+    }
+    if !failed.is_empty() {
+        utpm_bail!(UpdateFailed, failed.join(", "));
+    }
+    Ok(true)
+}
+
+/// Fetches the source of a single package and links its current version if it is new.
+async fn update(record: &mut Provenance) -> Result<()> {
+    utpm_log!(info, format!("Checking {}...", record.name), "source" => record.source.as_str());
+    if is_remote(&record.source) {
+        exist_git()?;
+    }
+
+    let tmp = temp_dir()?;
+    let path = tmp.path();
+
+    let commit = fetch_source(&record.source, record.reference.as_deref(), path)?;
+    let file = try_find(path)?;
+    if file.package.name != record.name {
+        utpm_log!(
+            warn,
+            "{} now provides {}, skipping",
+            record.source,
+            file.package.name
+        );
+        return Ok(());
+    }
+
+    let version = file.package.version.to_string();
+    let store = if record.namespace == "preview" {
+        package_cache_path()?
+    } else {
+        package_path()?
+    };
+    if check_path_dir(path!(store, &record.namespace, &record.name, &version)) {
+        utpm_log!(info, "= {}:{}", record.name, version);
+    } else {
+        link_fetched(path, &record.namespace, &record.name, &version).await?;
+        utpm_log!(info, "+ {}:{}", record.name, version);
+    }
+
+    record.commit = commit;
+    record.add_version(version);
+    record.save()
+}
@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use fmt_derive::{Debug, Display};
+use inquire::Confirm;
+use itertools::Itertools;
+use serde::Serialize;
+use tracing::instrument;
+use typst_kit::{download::Downloader, package::PackageStorage};
+use typst_syntax::package::PackageSpec;
+
+use crate::{
+    build, path,
+    utils::{
+        ProgressPrint,
+        checksum::{self, hash_dir},
+        dryrun::get_dry_run,
+        paths::{package_cache_path, package_path},
+        state::{Result, UtpmError},
+        store::{StoreLock, temp_dir},
+    },
+    utpm_bail, utpm_log,
+};
+
+use super::{
+    VerifyArgs,
+    clone::download,
+    list::{Entry, Kind, Store, read},
+};
+
+/// The outcome of the verification of a package version.
+#[derive(Serialize, Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// The content matches its checksum and the manifest matches its path.
+    #[display("ok")]
+    Ok,
+    /// The manifest is fine, but there is no checksum to compare the content to.
+    #[display("unchecked")]
+    Unchecked,
+    /// The content or the manifest is wrong.
+    #[display("corrupted")]
+    Corrupted,
+}
+
+/// A verified package version.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{status} @{namespace}/{name}:{version} [{store}]{}", if problems.is_empty() { String::new() } else { format!(": {}", problems.join(", ")) })]
+pub struct Checked {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+    pub store: Store,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+    /// Whether the version can be downloaded again from the registry.
+    #[serde(skip)]
+    pub downloadable: bool,
+}
+
+/// The result of `pkg verify`.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{}{} checked, {} corrupted, {} without checksum", packages.iter().map(|p| format!("{p}\n")).join(""), packages.len(), self.count(Status::Corrupted), self.count(Status::Unchecked))]
+pub struct Verification {
+    pub packages: Vec<Checked>,
+}
+
+impl Verification {
+    fn count(&self, status: Status) -> usize {
+        self.packages.iter().filter(|p| p.status == status).count()
+    }
+}
+
+/// Verifies the integrity of the packages in both stores.
+///
+/// Each version is hashed and compared to the checksum recorded when UTPM
+/// downloaded or linked it, or with `--remote` to a fresh download from the
+/// registry. Manifests are checked against their path and entrypoint. Corrupted
+/// downloads are offered for redownload, unless `--yes` is used.
+#[instrument(skip(cmd))]
+pub async fn run(cmd: &VerifyArgs) -> Result<bool> {
+    utpm_log!(trace, "executing verify command");
+    let local = package_path()?;
+    let cache = package_cache_path()?;
+    let mut entries = read(&local, Store::Local, &cmd.queries, true)?;
+    entries.extend(read(&cache, Store::Cache, &cmd.queries, true)?);
+
+    let mut packages = vec![];
+    for entry in entries {
+        let root = if entry.version.store == Store::Local {
+            &local
+        } else {
+            &cache
+        };
+        let dir = path!(root, &entry.namespace, &entry.name, &entry.version.version);
+        packages.push(check(&entry, &dir, cmd.remote)?);
+    }
+    let verification = Verification { packages };
+    utpm_log!(info, verification);
+
+    let corrupted = verification
+        .packages
+        .iter()
+        .filter(|p| p.status == Status::Corrupted)
+        .collect::<Vec<_>>();
+    let (downloadable, others): (Vec<&Checked>, Vec<&Checked>) =
+        corrupted.iter().partition(|checked| checked.downloadable);
+    for checked in others {
+        utpm_log!(
+            warn,
+            "@{}/{}:{} can't be downloaded again, link or install it again",
+            checked.namespace,
+            checked.name,
+            checked.version
+        );
+    }
+    if downloadable.is_empty() {
+        return Ok(corrupted.is_empty());
+    }
+
+    // Confirm with the user before replacing, unless `--yes` is provided.
+    if !cmd.yes {
+        let confirmed = Confirm::new("Download the corrupted packages again?")
+            .with_help_message(
+                downloadable
+                    .iter()
+                    .map(|c| format!("@{}/{}:{}", c.namespace, c.name, c.version))
+                    .join(", ")
+                    .as_str(),
+            )
+            .prompt();
+        if !matches!(confirmed, Ok(true)) {
+            return Ok(false);
+        }
+    }
+
+    let _lock = StoreLock::acquire()?;
+    for checked in downloadable {
+        utpm_log!(
+            info,
+            "Downloading @{}/{}:{}...",
+            checked.namespace,
+            checked.name,
+            checked.version
+        );
+        if !get_dry_run() {
+            download(
+                &spec(&checked.namespace, &checked.name, &checked.version)?,
+                &path!(&cache, &checked.namespace, &checked.name, &checked.version),
+            )?;
+        }
+    }
+    Ok(true)
+}
+
+/// Verifies a single package version living in `dir`.
+fn check(entry: &Entry, dir: &Path, remote: bool) -> Result<Checked> {
+    let version = &entry.version;
+    let mut problems = version.problems.clone();
+    let downloadable = version.kind == Kind::Download && entry.namespace == "preview";
+    let mut checked = false;
+
+    if version.broken {
+        problems.push("broken symlink".into());
+    } else if version.kind != Kind::Symlink {
+        let expected = if remote && downloadable {
+            Some(remote_checksum(
+                &entry.namespace,
+                &entry.name,
+                &version.version,
+            )?)
+        } else {
+            checksum::load(dir)?
+        };
+        if let Some(expected) = expected {
+            checked = true;
+            if hash_dir(dir)? != expected {
+                problems.push("checksum mismatch".into());
+            }
+        }
+    } else {
+        // The content of a symlink is the project itself, it can't be corrupted.
+        checked = true;
+    }
+
+    let status = if !problems.is_empty() {
+        Status::Corrupted
+    } else if checked {
+        Status::Ok
+    } else {
+        Status::Unchecked
+    };
+    Ok(Checked {
+        namespace: entry.namespace.clone(),
+        name: entry.name.clone(),
+        version: version.version.clone(),
+        store: version.store,
+        status,
+        problems,
+        downloadable,
+    })
+}
+
+/// Downloads a package from the registry into a temporary directory and hashes it.
+fn remote_checksum(namespace: &str, name: &str, version: &str) -> Result<String> {
+    let tmp = temp_dir()?;
+    let storage = PackageStorage::new(
+        Some(tmp.path().to_path_buf()),
+        None,
+        Downloader::new(format!("utpm/{}", build::COMMIT_HASH)),
+    );
+    let spec = spec(namespace, name, version)?;
+    let Ok(downloaded) = storage.prepare_package(&spec, &mut ProgressPrint::new(spec.to_string()))
+    else {
+        utpm_bail!(PackageNotExist);
+    };
+    hash_dir(&downloaded)
+}
+
+/// Builds the specification of a package version.
+fn spec(namespace: &str, name: &str, version: &str) -> Result<PackageSpec> {
+    format!("@{namespace}/{name}:{version}")
+        .parse::<PackageSpec>()
+        .map_err(|_| UtpmError::PackageFormatError)
+}
@@ -58,10 +58,16 @@ async fn main() {
 
                 ProjectArgs::Bump(cmd) => commands::bump::run(cmd).await,
 
+                ProjectArgs::Fetch(cmd) => commands::fetch::run(cmd).await,
+
                 ProjectArgs::Sync(cmd) => commands::sync::run(cmd).await,
                 ProjectArgs::Publish(cmd) => commands::publish::run(cmd).await,
 
                 ProjectArgs::Metadata(cmd) => commands::metadata::run(cmd).await,
+
+                ProjectArgs::Set(cmd) => commands::edit::run_set(cmd).await,
+                ProjectArgs::Add(cmd) => commands::edit::run_add(cmd).await,
+                ProjectArgs::Remove(cmd) => commands::edit::run_remove(cmd).await,
             },
             Commands::Packages(p) => match p {
                 PackagesArgs::List(cmd) => commands::list::run(cmd).await,
@@ -73,6 +79,11 @@ async fn main() {
                 PackagesArgs::Get(cmd) => commands::get::run(cmd).await,
 
                 PackagesArgs::Install(cmd) => commands::install::run(cmd).await,
+
+                PackagesArgs::Update(cmd) => commands::update::run(cmd).await,
+                PackagesArgs::Prune(cmd) => commands::prune::run(cmd).await,
+                PackagesArgs::Du(cmd) => commands::du::run(cmd).await,
+                PackagesArgs::Verify(cmd) => commands::verify::run(cmd).await,
             },
 
             Commands::Generate(cmd) => commands::generate::run(cmd).await,
@@ -86,6 +97,7 @@ async fn main() {
             Ok(_) => (),
             Err(err2) => error!("{err2}"), // If utpm_log errors, it will fallback to that
         };
+        std::process::exit(1);
     }
 }
 
@@ -1,23 +1,34 @@
 use std::fs::read_to_string;
+use std::io::{IsTerminal, Write};
 use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{fs, path::Path};
 
+use fmt_derive::{Debug, Display};
 use regex::Regex;
+use serde::Serialize;
 
 use std::io;
 use typst_kit::download::{DownloadState, Progress};
 use typst_syntax::package::PackageManifest;
 
+pub mod checksum;
 pub mod dryrun;
 pub mod git;
+pub mod imports;
 pub mod macros;
 pub mod output;
+pub mod package_ref;
 pub mod paths;
+pub mod provenance;
 pub mod specs;
 pub mod state;
+pub mod store;
+pub mod universe;
 
-use crate::utpm_bail;
+use crate::{utpm_bail, utpm_log};
 
+use self::output::{OutputFormat, get_output_format};
 use self::state::Result;
 
 /// Recursively copies a directory from a source to a destination.
@@ -51,6 +62,63 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<
     )
 }
 
+/// Computes the size of a directory and the last time something in it was modified.
+///
+/// Symlinks inside the directory are counted as links, not followed.
+pub fn dir_stats(path: impl AsRef<Path>) -> io::Result<(u64, Option<SystemTime>)> {
+    let path = path.as_ref();
+    let mut size = 0;
+    let mut modified = fs::metadata(path)?.modified().ok();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let (entry_size, entry_modified) = if meta.is_dir() {
+            dir_stats(entry.path())?
+        } else {
+            (meta.len(), meta.modified().ok())
+        };
+        size += entry_size;
+        modified = modified.max(entry_modified);
+    }
+    Ok((size, modified))
+}
+
+/// Formats a number of bytes for humans, e.g. `12.3 KiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Formats a point in time as a UTC date, e.g. `2025-01-31`.
+pub fn format_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    // Civil date from the number of days since 1970-01-01, see
+    // <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
 /// Finds the path to a `typst.toml` manifest file in the given directory.
 ///
 /// Returns an error if the manifest file does not exist.
@@ -135,19 +203,155 @@ pub fn write_manifest(data: &PackageManifest) -> Result<()> {
     Ok(())
 }
 
-//todo: impl
-/// A progress indicator for package downloads.
+/// Formats a duration for humans, e.g. `42s` or `3m 05s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// A progress event of a package download, emitted in structured output formats.
+#[derive(Serialize, Display, Debug, Clone)]
+#[display("{package}: {} / {}", format_size(*downloaded), total.map_or("?".into(), format_size))]
+pub struct DownloadProgress {
+    pub package: String,
+    /// Bytes downloaded so far.
+    pub downloaded: u64,
+    /// Size of the download, if the server told it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Current speed, in bytes per second.
+    pub speed: u64,
+    /// Estimated time left, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta: Option<u64>,
+    /// Seconds since the download started.
+    pub elapsed: u64,
+    pub done: bool,
+}
+
+impl DownloadProgress {
+    /// Computes the progress of `package` from the state of its download.
+    pub fn new(package: impl Into<String>, state: &DownloadState, done: bool) -> Self {
+        let speed = if state.bytes_per_second.is_empty() {
+            0
+        } else {
+            state.bytes_per_second.iter().sum::<usize>() / state.bytes_per_second.len()
+        };
+        let total = state.content_len.map(|len| len as u64);
+        let downloaded = state.total_downloaded as u64;
+        let eta = match total {
+            Some(total) if speed > 0 && !done => {
+                Some(total.saturating_sub(downloaded) / speed as u64)
+            },
+            _ => None,
+        };
+        Self {
+            package: package.into(),
+            downloaded,
+            total,
+            speed: speed as u64,
+            eta,
+            elapsed: state.start_time.elapsed().as_secs(),
+            done,
+        }
+    }
+
+    /// Describes the progress on a single line, e.g.
+    /// `@preview/cetz:0.3.1 1.2 MiB / 3.4 MiB, 512.0 KiB/s, 4s left`.
+    pub fn describe(&self) -> String {
+        let mut line = format!("{} {}", self.package, format_size(self.downloaded));
+        if let Some(total) = self.total {
+            line.push_str(&format!(" / {}", format_size(total)));
+        }
+        if self.done {
+            line.push_str(&format!(
+                " in {}",
+                format_duration(Duration::from_secs(self.elapsed))
+            ));
+        } else {
+            line.push_str(&format!(", {}/s", format_size(self.speed)));
+            if let Some(eta) = self.eta {
+                line.push_str(&format!(
+                    ", {} left",
+                    format_duration(Duration::from_secs(eta))
+                ));
+            }
+        }
+        line
+    }
+}
+
+/// Reports the progress of package downloads.
 ///
-/// (Warning) This is not fully implemented yet.
-/// It is intended to provide feedback to the user during package downloads.
-pub struct ProgressPrint {}
+/// In text mode, a line showing the downloaded bytes, the speed and the time left
+/// is kept up to date on the terminal. In structured output formats, a
+/// [`DownloadProgress`] event is logged every [`Self::EVENT_INTERVAL`] instead.
+pub struct ProgressPrint {
+    package: String,
+    last: Option<Instant>,
+}
+
+impl ProgressPrint {
+    /// How often the progress line is redrawn in text mode.
+    pub const LINE_INTERVAL: Duration = Duration::from_millis(200);
+    /// How often a progress event is logged in structured output formats.
+    pub const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Creates a reporter for the download of `package`.
+    pub fn new(package: impl Into<String>) -> Self {
+        Self {
+            package: package.into(),
+            last: None,
+        }
+    }
+
+    fn report(&mut self, state: &DownloadState, done: bool) -> Result<()> {
+        let text = get_output_format() == OutputFormat::Text;
+        let interval = if text {
+            Self::LINE_INTERVAL
+        } else {
+            Self::EVENT_INTERVAL
+        };
+        if !done && self.last.is_some_and(|last| last.elapsed() < interval) {
+            return Ok(());
+        }
+        self.last = Some(Instant::now());
+
+        let progress = DownloadProgress::new(&self.package, state, done);
+        if !text {
+            utpm_log!(info, progress);
+        } else if io::stderr().is_terminal() {
+            // Redraw the same line until the download is done.
+            let mut stderr = io::stderr();
+            let _ = write!(stderr, "\r\x1b[2K{}", progress.describe());
+            if done {
+                let _ = writeln!(stderr);
+            }
+            let _ = stderr.flush();
+        } else if done {
+            utpm_log!(info, "Downloaded {}", progress.describe());
+        }
+        Ok(())
+    }
+}
 
 impl Progress for ProgressPrint {
-    fn print_start(&mut self) {}
+    fn print_start(&mut self) {
+        self.last = None;
+    }
 
-    fn print_progress(&mut self, _state: &DownloadState) {}
+    fn print_progress(&mut self, state: &DownloadState) {
+        // Failing to report progress shouldn't stop the download.
+        let _ = self.report(state, false);
+    }
 
-    fn print_finish(&mut self, _state: &DownloadState) {}
+    fn print_finish(&mut self, state: &DownloadState) {
+        let _ = self.report(state, true);
+    }
 }
 
 mod tests {
@@ -0,0 +1,128 @@
+//! Checksums of package directories.
+//!
+//! A checksum is recorded whenever UTPM downloads or copies a package into one of
+//! the stores, so that `pkg verify` can tell later whether it has been damaged or
+//! edited. Records mirror the stores: the checksum of
+//! `<store>/<namespace>/<name>/<version>` lives in
+//! `checksums_path()/<local|cache>/<namespace>/<name>/<version>.sha256`.
+
+use std::fs::{self, create_dir_all, read_dir, read_to_string};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    path,
+    utils::{
+        dryrun::get_dry_run,
+        paths::{checksums_path, package_cache_path, package_path},
+        state::Result,
+        store::remove_path,
+    },
+};
+
+/// Hashes the content of a directory with SHA-256.
+///
+/// Paths are hashed relative to `path` and in a stable order, so the same content
+/// gives the same checksum wherever it is. Symlinks are hashed by target.
+pub fn hash_dir(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hash_into(&mut hasher, path, "")?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn hash_into(hasher: &mut Sha256, root: &Path, relative: &str) -> Result<()> {
+    let mut entries = read_dir(root.join(relative))?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = if relative.is_empty() {
+            name
+        } else {
+            format!("{relative}/{name}")
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            hasher.update(format!("l\0{relative}\0{}\0", target.display()));
+        } else if file_type.is_dir() {
+            hasher.update(format!("d\0{relative}\0"));
+            hash_into(hasher, root, &relative)?;
+        } else {
+            hasher.update(format!("f\0{relative}\0{}\0", entry.metadata()?.len()));
+            let mut file = fs::File::open(entry.path())?;
+            let mut buffer = [0; 8192];
+            loop {
+                let read = file.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Path of the record mirroring `dir`, without extension.
+///
+/// Returns `None` if `dir` is in neither of the package stores.
+fn record_base(dir: &Path) -> Result<Option<PathBuf>> {
+    let stores = [("cache", package_cache_path()?), ("local", package_path()?)];
+    for (store, root) in stores {
+        if let Ok(relative) = dir.strip_prefix(&root)
+            && !relative.as_os_str().is_empty()
+        {
+            return Ok(Some(path!(checksums_path()?, store, relative)));
+        }
+    }
+    Ok(None)
+}
+
+/// Path of the checksum recorded for the package version directory `dir`.
+fn record_path(dir: &Path) -> Result<Option<PathBuf>> {
+    Ok(record_base(dir)?.map(|base| base.with_added_extension("sha256")))
+}
+
+/// Hashes the package version directory `dir` and records its checksum.
+///
+/// Respects dry-run mode - if dry-run is enabled, nothing is written.
+pub fn save(dir: &Path) -> Result<()> {
+    if get_dry_run() {
+        return Ok(());
+    }
+    let Some(record) = record_path(dir)? else {
+        return Ok(());
+    };
+    let checksum = hash_dir(dir)?;
+    create_dir_all(record.parent().unwrap())?;
+    fs::write(record, checksum)?;
+    Ok(())
+}
+
+/// Loads the checksum recorded for the package version directory `dir`, if any.
+pub fn load(dir: &Path) -> Result<Option<String>> {
+    match record_path(dir)? {
+        Some(record) if record.is_file() => Ok(Some(read_to_string(record)?.trim().to_string())),
+        _ => Ok(None),
+    }
+}
+
+/// Forgets the checksums recorded for `dir`, be it a version, a package or a namespace.
+///
+/// Respects dry-run mode - if dry-run is enabled, nothing is removed.
+pub fn remove(dir: &Path) -> Result<()> {
+    if get_dry_run() {
+        return Ok(());
+    }
+    if let Some(base) = record_base(dir)? {
+        remove_path(&base)?;
+        remove_path(&base.with_added_extension("sha256"))?;
+    }
+    Ok(())
+}
@@ -1,149 +1,486 @@
-//! Git integration helpers (detect, clone/pull/push, add/commit) built on
-//! shelling out to the `git` CLI. A global state holds the project directory.
+//! Git integration helpers (detect, clone/pull/push, add/commit, tags).
+//!
+//! The operations UTPM needs are described by [`GitOps`]. [`Git`] implements them
+//! by shelling out to the `git` CLI through a [`GitRunner`], which checks their exit
+//! status and turns failures into [`UtpmError::Git`] with what git printed. It runs
+//! them in an explicit directory, and can be given a fake runner in tests.
+//!
+//! With the `git_lib` feature, `GitLib` implements them with libgit2 instead, so no
+//! `git` executable is needed. [`open`] picks the backend chosen with `--git-backend`.
 
-use crate::{
-    utils::{paths::get_current_dir, state::Result},
-    utpm_bail,
-};
 use std::{
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
-    sync::{Mutex, OnceLock},
 };
 
-/// Shared project state.
-/// Currently holds only the current working directory (String).
-pub struct State(pub PathBuf);
+use clap::ValueEnum;
+
+use crate::{
+    args::get_args,
+    utils::state::{Result, UtpmError},
+    utpm_bail,
+};
+
+/// The implementation used for git operations.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum GitBackend {
+    /// The `git` executable found in PATH.
+    #[default]
+    Cli,
+    /// libgit2, built in with the `git_lib` feature.
+    #[cfg(feature = "git_lib")]
+    Lib,
+}
+
+/// Returns the git backend chosen on the command line.
+pub fn get_git_backend() -> GitBackend {
+    get_args().git_backend
+}
 
-impl Default for State {
-    fn default() -> Self {
-        Self::new()
+/// Opens `dir` with the chosen git backend.
+pub fn open(dir: impl Into<PathBuf>) -> Box<dyn GitOps> {
+    match get_git_backend() {
+        GitBackend::Cli => Box::new(Git::new(dir)),
+        #[cfg(feature = "git_lib")]
+        GitBackend::Lib => Box::new(GitLib::new(dir)),
     }
 }
 
-impl State {
-    /// Build a new state using the process current directory.
-    /// Panics if `get_current_dir()` fails.
-    pub fn new() -> Self {
-        Self(get_current_dir().unwrap())
+/// The git operations UTPM performs on a repository.
+pub trait GitOps {
+    /// Clones `url` into `path`.
+    fn clone(&self, url: &str, path: &Path) -> Result<()>;
+
+    /// Pulls `branch` from `remote`.
+    fn pull(&self, remote: &str, branch: &str) -> Result<()>;
+
+    /// Pushes `branch` to `remote`.
+    fn push(&self, remote: &str, branch: &str) -> Result<()>;
+
+    /// Stages `path`.
+    fn add(&self, path: &str) -> Result<()>;
+
+    /// Commits the staged changes.
+    fn commit(&self, msg: &str) -> Result<()>;
+
+    /// Checks out `rev`.
+    fn checkout(&self, rev: &str) -> Result<()>;
+
+    /// Tags HEAD as `name`.
+    fn tag(&self, name: &str) -> Result<()>;
+
+    /// Returns the hash of the commit currently checked out.
+    fn head_commit(&self) -> Result<String>;
+
+    /// Returns the most recent tag reachable from HEAD, or None if there is none.
+    fn last_tag(&self) -> Result<Option<String>>;
+
+    /// Returns the subjects of the commits made since `since`, or of every commit,
+    /// newest first. Merge commits are left out.
+    fn log_subjects(&self, since: Option<&str>) -> Result<Vec<String>>;
+
+    /// Returns whether tracked files have uncommitted changes.
+    fn is_dirty(&self) -> Result<bool>;
+}
+
+/// Runs git commands.
+pub trait GitRunner {
+    /// Runs `git <args>` in `dir` and returns its standard output.
+    ///
+    /// Fails with [`UtpmError::Git`] if git exits with an error.
+    fn run(&self, dir: &Path, args: &[&str]) -> Result<String>;
+}
+
+/// Runs the `git` executable found in PATH.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GitCli;
+
+impl GitRunner for GitCli {
+    fn run(&self, dir: &Path, args: &[&str]) -> Result<String> {
+        let command = format!("git {}", args.join(" "));
+        if !dir.is_dir() {
+            utpm_bail!(
+                Git,
+                format!("`{command}` failed: {} is not a directory", dir.display())
+            );
+        }
+        let out = match Command::new("git").current_dir(dir).args(args).output() {
+            Ok(out) => out,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => utpm_bail!(GitNotFound),
+            Err(e) => utpm_bail!(Git, format!("`{command}` failed: {e}")),
+        };
+        if !out.status.success() {
+            let output = [out.stderr, out.stdout]
+                .iter()
+                .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string())
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            utpm_bail!(
+                Git,
+                format!("`{command}` failed ({}):\n{output}", out.status)
+            );
+        }
+        Ok(String::from_utf8(out.stdout)?)
     }
 }
 
-/// Return a global singleton `Mutex<State>`.
-/// Lazily initialized via `OnceLock` on first use.
-pub fn project() -> &'static Mutex<State> {
-    static STRING: OnceLock<Mutex<State>> = OnceLock::new();
-    STRING.get_or_init(|| Mutex::new(State::new()))
+/// Git commands run in a given directory.
+pub struct Git<R: GitRunner = GitCli> {
+    runner: R,
+    dir: PathBuf,
 }
 
-/// Check if Git is available in PATH by running `git --version`.
-///
-/// Returns:
-/// - Ok(true) if the command could be spawned (does not inspect exit status).
-/// - Err(GitNotFound) if the `git` executable is missing.
-/// - Err(Git(...)) for any other I/O error.
-pub fn exist_git() -> Result<bool> {
-    match Command::new("git").arg("--version").status() {
-        Ok(_) => Ok(true),
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {
-            utpm_bail!(GitNotFound)
-        },
-        Err(e) => {
-            utpm_bail!(Git, e.to_string())
-        },
+impl Git {
+    /// Runs the `git` executable in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_runner(GitCli, dir)
     }
 }
 
-/// Run `git push origin main` in the project directory.
-///
-/// Returns Ok(true) if the process launches; bubbles categorized errors otherwise.
-pub fn push_git() -> Result<bool> {
-    match Command::new("git")
-        .current_dir(&project().lock().unwrap().0)
-        .arg("push")
-        .arg("origin")
-        .arg("main")
-        .status()
-    {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            utpm_bail!(Git, e.to_string())
-        },
-    }
-}
-
-/// Run `git pull origin main` in the project directory.
-///
-/// Returns Ok(true) if the process launches; bubbles errors otherwise.
-pub fn pull_git() -> Result<bool> {
-    match Command::new("git")
-        .current_dir(&project().lock().unwrap().0)
-        .arg("pull")
-        .arg("origin")
-        .arg("main")
-        .status()
-    {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            utpm_bail!(Git, e.to_string())
-        },
-    }
-}
-
-/// Run `git clone <url> <path>` in the project directory.
-///
-/// Arguments:
-/// - `string`: repository URL.
-/// - `path`: destination path (relative to project dir or absolute).
+impl<R: GitRunner> Git<R> {
+    /// Runs commands with `runner` in `dir`.
+    pub fn with_runner(runner: R, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            runner,
+            dir: dir.into(),
+        }
+    }
+
+    /// The directory commands run in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The runner commands go through.
+    pub fn runner(&self) -> &R {
+        &self.runner
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String> {
+        self.runner.run(&self.dir, args)
+    }
+}
+
+impl<R: GitRunner> GitOps for Git<R> {
+    /// Run `git clone <url> <path>`.
+    fn clone(&self, url: &str, path: &Path) -> Result<()> {
+        self.run(&["clone", url, &path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Run `git pull <remote> <branch>`.
+    fn pull(&self, remote: &str, branch: &str) -> Result<()> {
+        self.run(&["pull", remote, branch])?;
+        Ok(())
+    }
+
+    /// Run `git push <remote> <branch>`.
+    fn push(&self, remote: &str, branch: &str) -> Result<()> {
+        self.run(&["push", remote, branch])?;
+        Ok(())
+    }
+
+    /// Run `git add <path>`.
+    fn add(&self, path: &str) -> Result<()> {
+        self.run(&["add", path])?;
+        Ok(())
+    }
+
+    /// Run `git commit -m <msg>`.
+    fn commit(&self, msg: &str) -> Result<()> {
+        self.run(&["commit", "-m", msg])?;
+        Ok(())
+    }
+
+    /// Run `git checkout <rev>`.
+    fn checkout(&self, rev: &str) -> Result<()> {
+        self.run(&["checkout", rev])?;
+        Ok(())
+    }
+
+    /// Run `git tag <name>`.
+    fn tag(&self, name: &str) -> Result<()> {
+        self.run(&["tag", name])?;
+        Ok(())
+    }
+
+    /// Run `git rev-parse HEAD`.
+    fn head_commit(&self) -> Result<String> {
+        Ok(self.run(&["rev-parse", "HEAD"])?.trim().to_string())
+    }
+
+    /// Run `git describe --tags --abbrev=0`.
+    fn last_tag(&self) -> Result<Option<String>> {
+        match self.run(&["describe", "--tags", "--abbrev=0"]) {
+            Ok(tag) => Ok(Some(tag.trim().to_string())),
+            // Git fails when no tag is reachable from HEAD.
+            Err(UtpmError::Git(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Run `git log --no-merges --format=%s [<since>..HEAD]`.
+    fn log_subjects(&self, since: Option<&str>) -> Result<Vec<String>> {
+        let range = since.map(|since| format!("{since}..HEAD"));
+        let mut args = vec!["log", "--no-merges", "--format=%s"];
+        args.extend(range.as_deref());
+        Ok(self.run(&args)?.lines().map(String::from).collect())
+    }
+
+    /// Run `git status --porcelain --untracked-files=no`.
+    fn is_dirty(&self) -> Result<bool> {
+        Ok(!self
+            .run(&["status", "--porcelain", "--untracked-files=no"])?
+            .trim()
+            .is_empty())
+    }
+}
+
+/// Git operations run with libgit2 in a given directory.
 ///
-/// Returns Ok(true) if the process launches; categorized errors otherwise.
-pub fn clone_git(string: &str, path: &str) -> Result<bool> {
-    match Command::new("git")
-        .current_dir(&project().lock().unwrap().0)
-        .arg("clone")
-        .arg(string)
-        .arg(path)
-        .status()
-    {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            utpm_bail!(Git, e.to_string())
-        },
-    }
-}
-
-/// Run `git add <path>` in the project directory.
+/// Remotes are reached over HTTPS, with the credentials from the configured
+/// credential helpers.
+#[cfg(feature = "git_lib")]
+pub struct GitLib {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "git_lib")]
+impl From<git2::Error> for UtpmError {
+    fn from(err: git2::Error) -> Self {
+        UtpmError::Git(err.message().to_string())
+    }
+}
+
+#[cfg(feature = "git_lib")]
+impl GitLib {
+    /// Runs operations on the repository containing `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory operations run in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Finds the repository containing the directory, like the CLI does.
+    fn repo(&self) -> Result<git2::Repository> {
+        Ok(git2::Repository::discover(&self.dir)?)
+    }
+
+    /// Turns `path`, relative to the directory, into a pathspec relative to the
+    /// root of the repository.
+    fn pathspec(&self, repo: &git2::Repository, path: &str) -> Result<Vec<String>> {
+        let Some(workdir) = repo.workdir() else {
+            utpm_bail!(Git, format!("{} is a bare repository", self.dir.display()));
+        };
+        let dir = self.dir.canonicalize()?;
+        let prefix = dir
+            .strip_prefix(workdir.canonicalize()?)
+            .unwrap_or(Path::new(""));
+        let spec = if path == "." {
+            prefix.to_path_buf()
+        } else {
+            prefix.join(path)
+        };
+        // An empty pathspec matches everything.
+        Ok(spec
+            .to_str()
+            .filter(|spec| !spec.is_empty())
+            .map(|spec| spec.replace('\\', "/"))
+            .into_iter()
+            .collect())
+    }
+}
+
+/// Answers credential requests with the configured credential helpers.
+#[cfg(feature = "git_lib")]
+fn callbacks(config: &git2::Config) -> git2::RemoteCallbacks<'_> {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+            git2::Cred::credential_helper(config, url, username)
+        } else {
+            git2::Cred::default()
+        }
+    });
+    callbacks
+}
+
+#[cfg(feature = "git_lib")]
+impl GitOps for GitLib {
+    fn clone(&self, url: &str, path: &Path) -> Result<()> {
+        let config = git2::Config::open_default()?;
+        let mut options = git2::FetchOptions::new();
+        options.remote_callbacks(callbacks(&config));
+        git2::build::RepoBuilder::new()
+            .fetch_options(options)
+            .clone(url, &self.dir.join(path))?;
+        Ok(())
+    }
+
+    /// Fetches `branch` and fast-forwards the current branch to it.
+    fn pull(&self, remote: &str, branch: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let config = repo.config()?;
+        let mut options = git2::FetchOptions::new();
+        options.remote_callbacks(callbacks(&config));
+        repo.find_remote(remote)?
+            .fetch(&[branch], Some(&mut options), None)?;
+
+        let fetched = repo.find_reference("FETCH_HEAD")?;
+        let fetched = repo.reference_to_annotated_commit(&fetched)?;
+        let (analysis, _) = repo.merge_analysis(&[&fetched])?;
+        if analysis.is_up_to_date() {
+            return Ok(());
+        }
+        if !analysis.is_fast_forward() && !analysis.is_unborn() {
+            utpm_bail!(Git, format!("Can't fast-forward to {remote}/{branch}"));
+        }
+        let head = repo.find_reference("HEAD")?;
+        let Some(current) = head.symbolic_target() else {
+            utpm_bail!(Git, "Can't pull with a detached HEAD".to_string());
+        };
+        let target = repo.find_object(fetched.id(), None)?;
+        repo.checkout_tree(&target, Some(git2::build::CheckoutBuilder::new().safe()))?;
+        repo.reference(current, fetched.id(), true, "pull: fast-forward")?;
+        Ok(())
+    }
+
+    fn push(&self, remote: &str, branch: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let config = repo.config()?;
+        let mut rejected = None;
+        let mut callbacks = callbacks(&config);
+        callbacks.push_update_reference(|name, status| {
+            if let Some(status) = status {
+                rejected = Some(format!("{name} was rejected: {status}"));
+            }
+            Ok(())
+        });
+        let mut options = git2::PushOptions::new();
+        options.remote_callbacks(callbacks);
+        repo.find_remote(remote)?.push(
+            &[format!("refs/heads/{branch}:refs/heads/{branch}")],
+            Some(&mut options),
+        )?;
+        drop(options);
+        if let Some(rejected) = rejected {
+            utpm_bail!(Git, rejected);
+        }
+        Ok(())
+    }
+
+    /// Stages the new, modified and deleted files under `path`.
+    fn add(&self, path: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let spec = self.pathspec(&repo, path)?;
+        let mut index = repo.index()?;
+        index.add_all(&spec, git2::IndexAddOption::DEFAULT, None)?;
+        index.update_all(&spec, None)?;
+        index.write()?;
+        Ok(())
+    }
+
+    fn commit(&self, msg: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let signature = repo.signature()?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        // There is no parent on an unborn branch.
+        let parent = repo
+            .head()
+            .ok()
+            .map(|head| head.peel_to_commit())
+            .transpose()?;
+        if let Some(parent) = &parent
+            && parent.tree_id() == tree.id()
+        {
+            utpm_bail!(Git, "Nothing to commit".to_string());
+        }
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, msg, &tree, &parents)?;
+        Ok(())
+    }
+
+    /// Checks out `rev`, or the remote branch `origin/<rev>`.
+    ///
+    /// Anything but a local branch is checked out as a detached HEAD.
+    fn checkout(&self, rev: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let (object, reference) = match repo.revparse_ext(rev) {
+            Ok(found) => found,
+            Err(_) => repo.revparse_ext(&format!("origin/{rev}"))?,
+        };
+        repo.checkout_tree(&object, Some(git2::build::CheckoutBuilder::new().safe()))?;
+        match reference.as_ref().and_then(|reference| reference.name()) {
+            Some(name) if name.starts_with("refs/heads/") => repo.set_head(name)?,
+            _ => repo.set_head_detached(object.peel_to_commit()?.id())?,
+        }
+        Ok(())
+    }
+
+    fn tag(&self, name: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let head = repo.head()?.peel(git2::ObjectType::Commit)?;
+        repo.tag_lightweight(name, &head, false)?;
+        Ok(())
+    }
+
+    fn head_commit(&self) -> Result<String> {
+        Ok(self.repo()?.head()?.peel_to_commit()?.id().to_string())
+    }
+
+    fn last_tag(&self) -> Result<Option<String>> {
+        let repo = self.repo()?;
+        let mut options = git2::DescribeOptions::new();
+        options.describe_tags();
+        match repo.describe(&options) {
+            Ok(found) => Ok(Some(
+                found.format(Some(git2::DescribeFormatOptions::new().abbreviated_size(0)))?,
+            )),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn log_subjects(&self, since: Option<&str>) -> Result<Vec<String>> {
+        let repo = self.repo()?;
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+        walk.push_head()?;
+        if let Some(since) = since {
+            walk.hide(repo.revparse_single(since)?.peel_to_commit()?.id())?;
+        }
+        let mut subjects = vec![];
+        for id in walk {
+            let commit = repo.find_commit(id?)?;
+            if commit.parent_count() <= 1 {
+                subjects.push(commit.summary().unwrap_or_default().to_string());
+            }
+        }
+        Ok(subjects)
+    }
+
+    fn is_dirty(&self) -> Result<bool> {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(false).include_ignored(false);
+        Ok(!self.repo()?.statuses(Some(&mut options))?.is_empty())
+    }
+}
+
+/// Check if Git is available in PATH by running `git --version`.
 ///
-/// Returns Ok(true) if the process launches; categorized errors otherwise.
-pub fn add_git(path: &str) -> Result<bool> {
-    match Command::new("git")
-        .current_dir(&project().lock().unwrap().0)
-        .arg("add")
-        .arg(path)
-        .status()
-    {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            utpm_bail!(Git, e.to_string())
-        },
-    }
-}
-
-/// Run `git commit -m <msg>` in the project directory.
+/// The library backend doesn't need the executable, so it is always available.
 ///
-/// Returns Ok(true) if the process launches; categorized errors otherwise.
-pub fn commit_git(msg: &str) -> Result<bool> {
-    match Command::new("git")
-        .current_dir(&project().lock().unwrap().0)
-        .arg("commit")
-        .arg("-m")
-        .arg(msg)
-        .status()
-    {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            utpm_bail!(Git, e.to_string())
-        },
+/// Returns:
+/// - Ok(true) if git runs successfully.
+/// - Err(GitNotFound) if the `git` executable is missing.
+/// - Err(Git(...)) for any other error.
+pub fn exist_git() -> Result<bool> {
+    if get_git_backend() == GitBackend::Cli {
+        GitCli.run(&std::env::temp_dir(), &["--version"])?;
     }
+    Ok(true)
 }
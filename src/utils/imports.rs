@@ -0,0 +1,75 @@
+//! Finds the packages a project imports.
+//!
+//! Package specifications (`@namespace/name:1.0.0`) are looked for anywhere in the
+//! scanned files, so this works for `#import`/`#include` in Typst files as well as
+//! for lists of packages such as lockfiles.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use ignore::{WalkBuilder, overrides::OverrideBuilder};
+use regex::Regex;
+use typst_syntax::package::PackageSpec;
+
+use crate::{utils::state::Result, utpm_log};
+
+/// Returns a regex for matching package specifications with a version, quoted or not.
+pub fn regex_spec() -> Regex {
+    Regex::new(r"@[A-Za-z0-9_-]+/[A-Za-z0-9_-]+:\d+\.\d+\.\d+").unwrap()
+}
+
+/// Finds every valid package specification in `content`.
+pub fn find_imports(content: &str) -> Vec<PackageSpec> {
+    regex_spec()
+        .find_iter(content)
+        .filter_map(|m| PackageSpec::from_str(m.as_str()).ok())
+        .collect()
+}
+
+/// Collects the packages imported in `path`.
+///
+/// A directory is walked for `.typ` files, respecting ignore files. Any other path
+/// is read as a single file. Unreadable and non-UTF-8 files are skipped.
+pub fn scan_imports(path: &Path) -> Result<HashSet<PackageSpec>> {
+    let mut specs = HashSet::new();
+    if !path.is_dir() {
+        specs.extend(read_imports(path)?);
+        return Ok(specs);
+    }
+
+    for file in typst_files(path)? {
+        specs.extend(read_imports(&file)?);
+    }
+    Ok(specs)
+}
+
+/// Lists the `.typ` files in the directory `dir`, respecting ignore files.
+pub fn typst_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut overrides = OverrideBuilder::new(dir);
+    overrides.add("*.typ")?;
+    let walker = WalkBuilder::new(dir).overrides(overrides.build()?).build();
+    let mut files = vec![];
+    for entry in walker {
+        let entry = entry?;
+        if entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Reads the imports of a single file, skipping it if it can't be read.
+pub fn read_imports(path: &Path) -> Result<Vec<PackageSpec>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(find_imports(&content)),
+        Err(err) => {
+            utpm_log!(warn, "Skipping {}: {}", path.display(), err);
+            Ok(vec![])
+        },
+    }
+}
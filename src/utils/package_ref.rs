@@ -0,0 +1,165 @@
+//! Parsing of the packages given on the command line.
+//!
+//! A reference can name a whole namespace (`@preview`), a package
+//! (`@preview/example` or just `example`), or versions of a package, exactly
+//! (`@preview/example:1.0.0`) or by range (`@preview/example:1` matches every
+//! `1.x.y`). Names and versions are validated with `typst_syntax`, so anything
+//! accepted here is accepted by Typst's own imports.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use ecow::EcoString;
+use typst_syntax::{
+    is_ident,
+    package::{PackageSpec, PackageVersion, VersionBound, VersionlessPackageSpec},
+};
+
+use crate::utils::state::{Result, UtpmError};
+
+/// The versions of a package a reference points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionReq {
+    /// No version given.
+    Any,
+    /// A full version, e.g. `1.0.0`.
+    Exact(PackageVersion),
+    /// A partial version, e.g. `1` or `1.2`, matching every version that starts with it.
+    Range(VersionBound),
+}
+
+impl VersionReq {
+    /// Whether `version` satisfies the requirement.
+    pub fn matches(&self, version: &PackageVersion) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => exact == version,
+            Self::Range(bound) => version.matches_eq(bound),
+        }
+    }
+}
+
+/// A namespace, package or package version given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageRef {
+    /// The namespace, if given with `@`.
+    pub namespace: Option<EcoString>,
+    /// The package name. Only missing for a whole namespace.
+    pub name: Option<EcoString>,
+    /// The requested versions. Always [`VersionReq::Any`] for a whole namespace.
+    pub version: VersionReq,
+}
+
+impl PackageRef {
+    /// The namespace, or `default` if none was given.
+    pub fn namespace_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.namespace.as_deref().unwrap_or(default)
+    }
+
+    /// Ensures the reference names a package, not a whole namespace.
+    pub fn require_name(&self) -> Result<&str> {
+        match &self.name {
+            Some(name) => Ok(name),
+            None => Err(self.invalid("a package name is required")),
+        }
+    }
+
+    /// The package, without version, with `default` as namespace if none was given.
+    pub fn versionless(&self, default: &str) -> Result<VersionlessPackageSpec> {
+        Ok(VersionlessPackageSpec {
+            namespace: self.namespace_or(default).into(),
+            name: self.require_name()?.into(),
+        })
+    }
+
+    /// The exact package version, with `default` as namespace if none was given.
+    pub fn spec(&self, default: &str) -> Result<PackageSpec> {
+        match self.version {
+            VersionReq::Exact(version) => Ok(self.versionless(default)?.at(version)),
+            _ => Err(self.invalid("an exact version such as 1.0.0 is required")),
+        }
+    }
+
+    /// Builds the error for this reference.
+    pub fn invalid(&self, reason: impl Into<String>) -> UtpmError {
+        UtpmError::InvalidPackage(self.to_string(), reason.into())
+    }
+}
+
+impl FromStr for PackageRef {
+    type Err = UtpmError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: EcoString| UtpmError::InvalidPackage(s.into(), reason.into());
+        let (package, version) = match s.split_once(':') {
+            Some((package, version)) => (package, Some(version)),
+            None => (s, None),
+        };
+
+        let version = match version {
+            None => VersionReq::Any,
+            Some("") => return Err(invalid("the version is empty".into())),
+            // Three components make a full version, fewer a range.
+            Some(version) if version.split('.').count() == 3 => {
+                VersionReq::Exact(version.parse().map_err(invalid)?)
+            },
+            Some(version) => VersionReq::Range(version.parse().map_err(invalid)?),
+        };
+
+        let (namespace, name) = match package.strip_prefix('@') {
+            Some(rest) => match rest.split_once('/') {
+                Some((namespace, name)) => (Some(namespace), Some(name)),
+                None => (Some(rest), None),
+            },
+            None => (None, Some(package)),
+        };
+
+        let Some(name) = name else {
+            let namespace = namespace.unwrap_or_default();
+            if namespace.is_empty() {
+                return Err(invalid("the namespace is missing".into()));
+            } else if !is_ident(namespace) {
+                return Err(invalid(
+                    format!("`{namespace}` is not a valid package namespace").into(),
+                ));
+            } else if version != VersionReq::Any {
+                return Err(invalid(
+                    "a version needs a package name, e.g. @preview/example:1.0.0".into(),
+                ));
+            }
+            return Ok(Self {
+                namespace: Some(namespace.into()),
+                name: None,
+                version,
+            });
+        };
+
+        // Let Typst validate the namespace and the name.
+        let spec = format!("@{}/{name}", namespace.unwrap_or("preview"));
+        let spec = VersionlessPackageSpec::from_str(&spec).map_err(invalid)?;
+        Ok(Self {
+            namespace: namespace.map(|_| spec.namespace),
+            name: Some(spec.name),
+            version,
+        })
+    }
+}
+
+impl Display for PackageRef {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(namespace) = &self.namespace {
+            write!(f, "@{namespace}")?;
+            if self.name.is_some() {
+                write!(f, "/")?;
+            }
+        }
+        if let Some(name) = &self.name {
+            write!(f, "{name}")?;
+        }
+        match self.version {
+            VersionReq::Any => Ok(()),
+            VersionReq::Exact(version) => write!(f, ":{version}"),
+            VersionReq::Range(bound) => write!(f, ":{bound}"),
+        }
+    }
+}
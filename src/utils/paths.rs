@@ -16,6 +16,18 @@ pub const UTPM_SUBDIR: &str = "utpm";
 pub const MANIFEST_FILE: &str = "typst.toml";
 /// The subdirectory for locally cloned git packages.
 pub const LOCAL_PACKAGES: &str = "git-packages";
+/// The subdirectory for the records of development links.
+pub const DEV_LINKS_SUBDIR: &str = "dev-links";
+/// The subdirectory for the records of packages copied by `prj link`.
+pub const LINKED_COPIES_SUBDIR: &str = "linked-copies";
+/// The subdirectory for temporary files.
+pub const TMP_SUBDIR: &str = "tmp";
+/// The name of the lock file guarding the package stores.
+pub const LOCK_FILE: &str = "store.lock";
+/// The subdirectory for the provenance records of installed packages.
+pub const PROVENANCE_SUBDIR: &str = "provenance";
+/// The subdirectory for the checksums of package directories.
+pub const CHECKSUMS_SUBDIR: &str = "checksums";
 
 fn env_path(key: &str) -> Option<PathBuf> {
     env::var(key).ok().map(PathBuf::from)
@@ -62,6 +74,26 @@ pub fn local_package_path() -> Result<PathBuf> {
     Ok(path!(utpm_data_path()?, LOCAL_PACKAGES))
 }
 
+/// Gets the path to the directory holding the provenance records of installed packages.
+pub fn provenance_path() -> Result<PathBuf> {
+    Ok(path!(utpm_data_path()?, PROVENANCE_SUBDIR))
+}
+
+/// Gets the path to the directory holding the records of development links.
+pub fn dev_links_path() -> Result<PathBuf> {
+    Ok(path!(utpm_data_path()?, DEV_LINKS_SUBDIR))
+}
+
+/// Gets the path to the directory holding the records of packages copied by `prj link`.
+pub fn linked_copies_path() -> Result<PathBuf> {
+    Ok(path!(utpm_data_path()?, LINKED_COPIES_SUBDIR))
+}
+
+/// Gets the path to the directory holding the checksums of package directories.
+pub fn checksums_path() -> Result<PathBuf> {
+    Ok(path!(utpm_data_path()?, CHECKSUMS_SUBDIR))
+}
+
 /// Gets the current working directory.
 ///
 /// This path can be overridden by setting the `UTPM_CURRENT_DIR` environment variable.
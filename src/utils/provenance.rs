@@ -0,0 +1,301 @@
+//! Provenance records for packages installed with `pkg install`.
+//!
+//! Each record remembers where a package came from (URL or local path, ref and
+//! commit) so that `pkg update` can fetch the source again later. Records are
+//! stored as small TOML files in `provenance_path()/<namespace>/<name>.toml`.
+//!
+//! Development links made with `prj link --as-version` or `--alias` are recorded
+//! the same way in `dev_links_path()/<namespace>/<name>/<version>.toml`, and the
+//! copies made by `prj link` in `linked_copies_path()/<namespace>/<name>/<version>.toml`.
+
+use std::fs::{self, create_dir_all, read_dir, read_to_string, remove_file};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use typst_syntax::package::PackageVersion;
+
+use crate::{
+    path,
+    utils::{
+        dryrun::get_dry_run,
+        paths::{
+            check_path_dir, check_path_file, dev_links_path, linked_copies_path, provenance_path,
+        },
+        state::Result,
+        store::remove_path,
+    },
+};
+
+/// Loads the record at `path`, if there is one.
+fn load_record<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !check_path_file(path) {
+        return Ok(None);
+    }
+    Ok(Some(toml::from_str(&read_to_string(path)?)?))
+}
+
+/// Loads every record in `dir`, or nothing if it doesn't exist.
+fn load_records<T: DeserializeOwned>(dir: &Path) -> Result<Vec<T>> {
+    let mut records = vec![];
+    if !check_path_dir(dir) {
+        return Ok(records);
+    }
+    for file in read_dir(dir)? {
+        let file = file?.path();
+        if file.extension().is_some_and(|ext| ext == "toml") {
+            records.push(toml::from_str(&read_to_string(file)?)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Writes a record to `path`.
+///
+/// Respects dry-run mode - if dry-run is enabled, nothing is written.
+fn save_record(path: &Path, record: &impl Serialize) -> Result<()> {
+    if get_dry_run() {
+        return Ok(());
+    }
+    create_dir_all(path.parent().unwrap())?;
+    fs::write(path, toml::to_string_pretty(record)?)?;
+    Ok(())
+}
+
+/// Removes the record at `path`, if there is one.
+///
+/// Respects dry-run mode - if dry-run is enabled, nothing is removed.
+fn remove_record(path: &Path) -> Result<()> {
+    if !get_dry_run() && check_path_file(path) {
+        remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Where an installed package comes from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Provenance {
+    /// Namespace the package has been installed into.
+    pub namespace: String,
+    /// Name of the package, as written in its manifest.
+    pub name: String,
+    /// Git URL or local path the package has been installed from.
+    pub source: String,
+    /// Branch, tag or commit requested at install time.
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// Commit checked out during the last install or update.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Versions installed from this source.
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+impl Provenance {
+    /// Creates a new record without any installed version.
+    pub fn new(
+        namespace: impl Into<String>,
+        name: impl Into<String>,
+        source: impl Into<String>,
+        reference: Option<String>,
+        commit: Option<String>,
+    ) -> Self {
+        Self {
+            namespace: namespace.into(),
+            name: name.into(),
+            source: source.into(),
+            reference,
+            commit,
+            versions: vec![],
+        }
+    }
+
+    /// Path of the record for a package.
+    pub fn path(namespace: &str, name: &str) -> Result<PathBuf> {
+        Ok(path!(provenance_path()?, namespace, format!("{name}.toml")))
+    }
+
+    /// Loads the record of a package, if there is one.
+    pub fn load(namespace: &str, name: &str) -> Result<Option<Self>> {
+        load_record(&Self::path(namespace, name)?)
+    }
+
+    /// Loads every record, sorted by namespace and name.
+    pub fn all() -> Result<Vec<Self>> {
+        let root = provenance_path()?;
+        let mut records = vec![];
+        if !check_path_dir(&root) {
+            return Ok(records);
+        }
+        for namespace in read_dir(root)? {
+            let namespace = namespace?;
+            if namespace.file_type()?.is_dir() {
+                records.extend(load_records(&namespace.path())?);
+            }
+        }
+        records.sort_by(|a: &Self, b: &Self| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+        Ok(records)
+    }
+
+    /// Remembers an installed version, keeping the list sorted and unique.
+    pub fn add_version(&mut self, version: impl Into<String>) {
+        let version = version.into();
+        if !self.versions.contains(&version) {
+            self.versions.push(version);
+            self.versions
+                .sort_by_key(|v| PackageVersion::from_str(v).ok());
+        }
+    }
+
+    /// Writes the record to disk.
+    ///
+    /// Respects dry-run mode - if dry-run is enabled, nothing is written.
+    pub fn save(&self) -> Result<()> {
+        save_record(&Self::path(&self.namespace, &self.name)?, self)
+    }
+
+    /// Forgets the installed versions of a namespace, a package or a single
+    /// version, so that `pkg update` doesn't bring them back.
+    ///
+    /// A record is removed once none of its versions is left. Respects dry-run
+    /// mode - if dry-run is enabled, nothing is changed.
+    pub fn forget(namespace: &str, name: Option<&str>, version: Option<&str>) -> Result<()> {
+        let Some(name) = name else {
+            if !get_dry_run() {
+                remove_path(&path!(provenance_path()?, namespace))?;
+            }
+            return Ok(());
+        };
+        let Some(mut record) = Self::load(namespace, name)? else {
+            return Ok(());
+        };
+        record
+            .versions
+            .retain(|v| version.is_some_and(|version| v != version));
+        if record.versions.is_empty() {
+            remove_record(&Self::path(namespace, name)?)
+        } else {
+            record.save()
+        }
+    }
+}
+
+/// A record kept for a linked package version, in
+/// `root()/<namespace>/<name>/<version>.toml`.
+pub trait VersionRecord: Serialize + DeserializeOwned {
+    /// The directory holding the records of this kind.
+    fn root() -> Result<PathBuf>;
+
+    /// The namespace, name and version the record is about.
+    fn key(&self) -> (&str, &str, &str);
+
+    /// Path of the record for a linked version.
+    fn path(namespace: &str, name: &str, version: &str) -> Result<PathBuf> {
+        Ok(path!(
+            Self::root()?,
+            namespace,
+            name,
+            format!("{version}.toml")
+        ))
+    }
+
+    /// Loads the record of a linked version, if there is one.
+    fn load(namespace: &str, name: &str, version: &str) -> Result<Option<Self>> {
+        load_record(&Self::path(namespace, name, version)?)
+    }
+
+    /// Loads the records of every linked version of a package.
+    fn all(namespace: &str, name: &str) -> Result<Vec<Self>> {
+        load_records(&path!(Self::root()?, namespace, name))
+    }
+
+    /// Writes the record to disk.
+    ///
+    /// Respects dry-run mode - if dry-run is enabled, nothing is written.
+    fn save(&self) -> Result<()> {
+        let (namespace, name, version) = self.key();
+        save_record(&Self::path(namespace, name, version)?, self)
+    }
+
+    /// Forgets a linked version.
+    ///
+    /// Respects dry-run mode - if dry-run is enabled, nothing is removed.
+    fn remove(namespace: &str, name: &str, version: &str) -> Result<()> {
+        remove_record(&Self::path(namespace, name, version)?)
+    }
+}
+
+/// A package version linked under a version or alias other than the one of its manifest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DevLink {
+    /// Namespace the package has been linked into.
+    pub namespace: String,
+    /// Name of the package.
+    pub name: String,
+    /// Version directory the package has been linked into.
+    pub version: String,
+    /// Version written in the manifest of the linked project.
+    pub manifest_version: String,
+    /// Alias given with `--alias`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Path of the linked project.
+    pub source: String,
+}
+
+impl VersionRecord for DevLink {
+    fn root() -> Result<PathBuf> {
+        dev_links_path()
+    }
+
+    fn key(&self) -> (&str, &str, &str) {
+        (&self.namespace, &self.name, &self.version)
+    }
+}
+
+/// A package version copied into a store by `prj link`.
+///
+/// Tells linked copies apart from downloads, which live in the same cache.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkedCopy {
+    /// Namespace the package has been linked into.
+    pub namespace: String,
+    /// Name of the package.
+    pub name: String,
+    /// Version directory the package has been linked into.
+    pub version: String,
+    /// Path of the linked project.
+    pub source: String,
+}
+
+impl VersionRecord for LinkedCopy {
+    fn root() -> Result<PathBuf> {
+        linked_copies_path()
+    }
+
+    fn key(&self) -> (&str, &str, &str) {
+        (&self.namespace, &self.name, &self.version)
+    }
+}
+
+/// Forgets the link records of a namespace, a package or a single version, be
+/// they development links or copies.
+///
+/// Respects dry-run mode - if dry-run is enabled, nothing is removed.
+pub fn forget_links(namespace: &str, name: Option<&str>, version: Option<&str>) -> Result<()> {
+    if get_dry_run() {
+        return Ok(());
+    }
+    for root in [DevLink::root()?, LinkedCopy::root()?] {
+        let mut path = path!(root, namespace);
+        if let Some(name) = name {
+            path.push(name);
+            if let Some(version) = version {
+                path.push(format!("{version}.toml"));
+            }
+        }
+        remove_path(&path)?;
+    }
+    Ok(())
+}
@@ -2,6 +2,8 @@ use ecow::EcoVec;
 use serde::{Deserialize, Serialize};
 use typst_syntax::package::ToolInfo;
 
+use super::state::{Result, UtpmError};
+
 /// Represents the `[tool.utpm]` section in the `typst.toml` manifest.
 ///
 /// This struct holds UTPM-specific configuration for a package.
@@ -9,19 +11,66 @@ use typst_syntax::package::ToolInfo;
 pub struct Extra {
     /// A list of file patterns to exclude when publishing the package.
     pub exclude: Option<EcoVec<String>>,
+    /// A changelog to which `prj bump` adds a section, e.g. `CHANGELOG.md`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<String>,
+}
+
+/// Represents the `[tool.utpm.bump]` section, configuring `prj bump`.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct BumpConfig {
+    /// Files bumped along with `typst.toml`, relative to it.
+    pub files: Vec<String>,
+    /// The tag around versions to bump, like `--tag`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// The message of the commit made with `--commit`, `{version}` standing for
+    /// the new version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The name of the git tag made with `--tag-git`, `{version}` standing for
+    /// the new version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_tag: Option<String>,
+    /// Regexes matching the versions to replace in the bumped files, where
+    /// `{version}` stands for the current version.
+    pub patterns: Vec<String>,
+}
+
+impl BumpConfig {
+    /// Reads the `[tool.utpm.bump]` section of a manifest, if there is one.
+    ///
+    /// Fails if the section doesn't match `BumpConfig`, rather than falling back
+    /// to the defaults.
+    pub fn from_tool(tool: &ToolInfo) -> Result<Option<Self>> {
+        let Some(bump) = tool.sections.get("utpm").and_then(|utpm| utpm.get("bump")) else {
+            return Ok(None);
+        };
+        bump.clone()
+            .try_into()
+            .map(Some)
+            .map_err(|err: toml::de::Error| UtpmError::BumpConfig(err.message().into()))
+    }
 }
 
 impl Default for Extra {
     /// Creates a default `Extra` instance.
     fn default() -> Self {
-        Self { exclude: None }
+        Self {
+            exclude: None,
+            changelog: None,
+        }
     }
 }
 
 impl Extra {
     /// Creates a new `Extra` instance with the given configuration.
     pub fn new(exclude: Option<EcoVec<String>>) -> Self {
-        Self { exclude }
+        Self {
+            exclude,
+            ..Default::default()
+        }
     }
 }
 
@@ -54,6 +103,10 @@ impl From<ToolInfo> for Extra {
                     .map(|f| f.to_string())
                     .collect::<EcoVec<_>>()
             }),
+            changelog: a
+                .get("changelog")
+                .and_then(|c| c.as_str())
+                .map(String::from),
         }
     }
 }
@@ -102,6 +102,66 @@ pub enum UtpmError {
     )]
     PackageFormatError,
 
+    /// An error for a package reference given on the command line that can't be used.
+    #[error(
+        "Invalid package `{0}`: {1}.\n\nAccepted formats:\n  - Full: @namespace/package:1.0.0\n  - Version range: @namespace/package:1 or @namespace/package:1.2\n  - Without version: @namespace/package\n  - Namespace only: @namespace\n\nExample: @preview/example:1.0.0"
+    )]
+    InvalidPackage(String, String),
+
+    /// An error for a version that can't be parsed or bumped.
+    #[error(
+        "Invalid version `{0}`: {1}.\nUse major, minor, patch or a full version.\nExample: utpm prj bump minor"
+    )]
+    InvalidVersion(String, String),
+
+    /// An error for a `[tool.utpm.bump]` section that can't be read.
+    #[error("Invalid [tool.utpm.bump] in typst.toml: {0}")]
+    BumpConfig(String),
+
+    /// An error for a replacement pattern of `[tool.utpm.bump]` that can't be used.
+    #[error("Invalid bump pattern `{0}` in typst.toml: {1}")]
+    BumpPattern(String, String),
+
+    /// An error when git would commit over uncommitted changes.
+    #[error(
+        "The working tree has uncommitted changes. Commit or stash them first, or use --allow-dirty."
+    )]
+    DirtyTree,
+
+    /// An error when a bump would lower the version.
+    #[error("Bumping from {0} to {1} is a downgrade. Use --allow-downgrade to do it anyway.")]
+    Downgrade(PackageVersion, PackageVersion),
+
+    /// An error when `prj set`, `prj add` or `prj remove` can't edit the manifest.
+    #[error("Can't edit `{0}` in typst.toml: {1}.")]
+    ManifestEdit(String, String),
+
+    /// An error for a category Typst Universe doesn't know.
+    #[error("`{0}` is not a Typst Universe category. {1}")]
+    InvalidCategory(String, String),
+
+    /// An error for a discipline Typst Universe doesn't know.
+    #[error("`{0}` is not a Typst Universe discipline. {1}")]
+    InvalidDiscipline(String, String),
+
+    /// An error when `pkg prune` finds no import, so every cached package would go.
+    #[error(
+        "No package import found in {0}. Refusing to remove the whole cache.\nRun `utpm pkg prune` in your projects, or give their paths."
+    )]
+    NoImports(String),
+
+    /// An error when `prj fetch` couldn't provide every dependency.
+    #[error("Some packages are missing: {0}")]
+    MissingPackages(String),
+
+    /// An error when `pkg update` couldn't update some packages.
+    #[error("Some packages couldn't be updated: {0}")]
+    UpdateFailed(String),
+
+    /// An error when a field asked with `prj metadata` can't be read.
+    #[error("Can't read `{0}` from typst.toml: {1}.")]
+    MetadataField(String, String),
+
     #[error("There is no files in the new package. You should change your ignored files.")]
     NoFiles,
 
@@ -187,6 +247,19 @@ impl UtpmError {
             FromUTF8Error(_) => "FromUTF8Error",
             GitNotFound => "GitNotFound",
             PackageFormatError => "PackageFormatError",
+            InvalidPackage(_, _) => "InvalidPackage",
+            InvalidVersion(_, _) => "InvalidVersion",
+            Downgrade(_, _) => "Downgrade",
+            BumpConfig(_) => "BumpConfig",
+            BumpPattern(_, _) => "BumpPattern",
+            DirtyTree => "DirtyTree",
+            MetadataField(_, _) => "MetadataField",
+            NoImports(_) => "NoImports",
+            MissingPackages(_) => "MissingPackages",
+            UpdateFailed(_) => "UpdateFailed",
+            ManifestEdit(_, _) => "ManifestEdit",
+            InvalidCategory(_, _) => "InvalidCategory",
+            InvalidDiscipline(_, _) => "InvalidDiscipline",
             NoFiles => "NoFiles",
             OmitedTypstFile(_) => "OmitedTypstFile",
             OmitedEntryfile(_, _) => "OmitedEntryfile",
@@ -0,0 +1,214 @@
+//! Helpers to write safely into the package stores.
+//!
+//! Every operation gets its own temporary directory, new package directories are
+//! staged next to their destination and swapped into place (keeping the previous
+//! version until the swap succeeded), and an advisory lock keeps concurrent UTPM
+//! processes from writing into the stores at the same time.
+
+use std::fs::{self, File, OpenOptions, create_dir_all};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tempfile::{Builder, TempDir};
+
+use crate::{
+    path,
+    utils::{
+        dryrun::get_dry_run,
+        paths::{LOCK_FILE, TMP_SUBDIR, utpm_data_path},
+        state::Result,
+        symlink_all,
+    },
+    utpm_log,
+};
+
+/// Creates a unique temporary directory in UTPM's data directory.
+///
+/// The directory and its content are removed when the returned value is dropped.
+pub fn temp_dir() -> Result<TempDir> {
+    let root = path!(utpm_data_path()?, TMP_SUBDIR);
+    create_dir_all(&root)?;
+    Ok(Builder::new().prefix("utpm-").tempdir_in(root)?)
+}
+
+/// Creates a hidden directory next to `destination` to stage its new content.
+///
+/// Being on the same filesystem, it can then be moved into place with a rename by
+/// [`commit_staged`]. It is removed on drop if it never gets committed.
+pub fn stage_dir(destination: &Path) -> Result<TempDir> {
+    let parent = destination.parent().unwrap();
+    create_dir_all(parent)?;
+    Ok(Builder::new().prefix(".utpm-stage-").tempdir_in(parent)?)
+}
+
+/// Builds a unique hidden path next to `destination`.
+fn sibling(destination: &Path, tag: &str) -> PathBuf {
+    let name = destination
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    destination.with_file_name(format!(".utpm-{tag}-{name}-{}-{nanos}", process::id()))
+}
+
+/// Removes whatever is at `path`, be it a directory, a symlink or a file.
+pub fn remove_path(path: &Path) -> io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.is_file() => fs::remove_file(path),
+        Ok(_) => fs::remove_dir_all(path),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Moves `staged` to `destination`, swapping out whatever was there before.
+///
+/// The previous content is set aside next to `destination` and only deleted once
+/// the new content is in place. If the swap fails, it is restored.
+pub fn replace(staged: &Path, destination: &Path) -> Result<()> {
+    if destination.symlink_metadata().is_err() {
+        fs::rename(staged, destination)?;
+        return Ok(());
+    }
+
+    let backup = sibling(destination, "old");
+    fs::rename(destination, &backup)?;
+    if let Err(err) = fs::rename(staged, destination) {
+        fs::rename(&backup, destination)?;
+        return Err(err.into());
+    }
+    if let Err(err) = remove_path(&backup) {
+        utpm_log!(
+            warn,
+            "Couldn't remove the previous version at {}: {}",
+            backup.display(),
+            err
+        );
+    }
+    Ok(())
+}
+
+/// Moves a staged directory to `destination`, replacing the previous version if any.
+///
+/// See [`replace`]. The staged directory is removed if the swap fails.
+pub fn commit_staged(staged: TempDir, destination: &Path) -> Result<PathBuf> {
+    let staged = staged.keep();
+    if let Err(err) = replace(&staged, destination) {
+        remove_path(&staged)?;
+        return Err(err);
+    }
+    Ok(destination.to_path_buf())
+}
+
+/// Creates a symlink to `origin` next to `destination`, then swaps it into place.
+///
+/// See [`replace`]. The staged symlink is removed if the swap fails.
+pub fn commit_symlink(origin: &Path, destination: &Path) -> Result<PathBuf> {
+    create_dir_all(destination.parent().unwrap())?;
+    let staged = sibling(destination, "stage");
+    symlink_all(origin, &staged)?;
+    if let Err(err) = replace(&staged, destination) {
+        remove_path(&staged)?;
+        return Err(err);
+    }
+    Ok(destination.to_path_buf())
+}
+
+/// Writes several files at once, leaving them all as they were if any write fails.
+///
+/// The new contents are staged next to their files first, then swapped into
+/// place in order, so the file that matters most should come last. If a swap
+/// fails, the files already replaced get their previous content back.
+pub fn write_files(files: &[(PathBuf, String)]) -> Result<()> {
+    let mut staged = vec![];
+    let mut previous = vec![];
+    let prepared = files.iter().try_for_each(|(path, content)| {
+        previous.push(match fs::read(path) {
+            Ok(before) => Some(before),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        });
+        let stage = sibling(path, "stage");
+        staged.push(stage.clone());
+        fs::write(stage, content)
+    });
+    if let Err(err) = prepared {
+        for stage in &staged {
+            let _ = remove_path(stage);
+        }
+        return Err(err.into());
+    }
+
+    for (i, ((path, _), stage)) in files.iter().zip(&staged).enumerate() {
+        if let Err(err) = fs::rename(stage, path) {
+            for ((path, _), before) in files.iter().zip(&previous).take(i) {
+                let restored = match before {
+                    Some(before) => fs::write(path, before),
+                    None => remove_path(path),
+                };
+                if let Err(err) = restored {
+                    utpm_log!(warn, "Couldn't restore {}: {}", path.display(), err);
+                }
+            }
+            for stage in &staged[i..] {
+                let _ = remove_path(stage);
+            }
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+/// An exclusive advisory lock on the package stores, released on drop.
+///
+/// Nothing is locked in dry-run mode.
+pub struct StoreLock(Option<File>);
+
+impl StoreLock {
+    /// Acquires the lock, waiting for other UTPM processes to release it.
+    pub fn acquire() -> Result<Self> {
+        if get_dry_run() {
+            return Ok(Self(None));
+        }
+        let path = path!(utpm_data_path()?, LOCK_FILE);
+        create_dir_all(path.parent().unwrap())?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {},
+            Err(fs::TryLockError::WouldBlock) => {
+                utpm_log!(
+                    info,
+                    "Waiting for another utpm process to release the package store..."
+                );
+                file.lock()?;
+            },
+            Err(fs::TryLockError::Error(err)) if err.kind() == ErrorKind::Unsupported => {
+                utpm_log!(
+                    warn,
+                    "File locking is not supported here, continuing without lock"
+                );
+                return Ok(Self(None));
+            },
+            Err(fs::TryLockError::Error(err)) => return Err(err.into()),
+        }
+        utpm_log!(trace, "Package store locked", "lock" => path.display().to_string());
+        Ok(Self(Some(file)))
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.0 {
+            let _ = file.unlock();
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! The categories and disciplines accepted by Typst Universe.
+//!
+//! The lists follow the [manifest documentation] of the typst/packages repository.
+//!
+//! [manifest documentation]: https://github.com/typst/packages/blob/main/docs/manifest.md
+
+use typst_syntax::package::PackageInfo;
+
+use crate::{
+    utils::state::{Result, UtpmError},
+    utpm_bail,
+};
+
+/// The categories a package can be listed under.
+pub const CATEGORIES: &[&str] = &[
+    "components",
+    "visualization",
+    "model",
+    "layout",
+    "text",
+    "languages",
+    "scripting",
+    "integration",
+    "utility",
+    "fun",
+    "book",
+    "report",
+    "paper",
+    "thesis",
+    "poster",
+    "flyer",
+    "presentation",
+    "cv",
+    "office",
+];
+
+/// The disciplines a package can target.
+pub const DISCIPLINES: &[&str] = &[
+    "agriculture",
+    "anthropology",
+    "archaeology",
+    "architecture",
+    "biology",
+    "business",
+    "chemistry",
+    "communication",
+    "computer-science",
+    "design",
+    "drawing",
+    "economics",
+    "education",
+    "engineering",
+    "fashion",
+    "film",
+    "geography",
+    "geology",
+    "history",
+    "journalism",
+    "law",
+    "linguistics",
+    "literature",
+    "mathematics",
+    "medicine",
+    "music",
+    "painting",
+    "philosophy",
+    "photography",
+    "physics",
+    "politics",
+    "psychology",
+    "sociology",
+    "theater",
+    "theology",
+    "transportation",
+];
+
+/// Fails if `category` isn't one of [`CATEGORIES`].
+pub fn check_category(category: &str) -> Result<()> {
+    if !CATEGORIES.contains(&category) {
+        utpm_bail!(
+            InvalidCategory,
+            category.into(),
+            help(category, CATEGORIES, "categories")
+        );
+    }
+    Ok(())
+}
+
+/// Fails if `discipline` isn't one of [`DISCIPLINES`].
+pub fn check_discipline(discipline: &str) -> Result<()> {
+    if !DISCIPLINES.contains(&discipline) {
+        utpm_bail!(
+            InvalidDiscipline,
+            discipline.into(),
+            help(discipline, DISCIPLINES, "disciplines")
+        );
+    }
+    Ok(())
+}
+
+/// Returns the problems with the categories and disciplines of a package.
+pub fn lint(package: &PackageInfo) -> Vec<UtpmError> {
+    let categories = package.categories.iter().map(|c| check_category(c));
+    let disciplines = package.disciplines.iter().map(|d| check_discipline(d));
+    categories
+        .chain(disciplines)
+        .filter_map(Result::err)
+        .collect()
+}
+
+/// Suggests the closest value of `list`, or lists all of them.
+fn help(value: &str, list: &[&'static str], name: &str) -> String {
+    match suggest(value, list) {
+        Some(suggestion) => format!("Did you mean `{suggestion}`?"),
+        None => format!("Valid {name}: {}", list.join(", ")),
+    }
+}
+
+/// Finds the value of `list` that `value` is most likely a typo of.
+///
+/// Values are compared without case. A value matches if it is at most two edits
+/// away (and not entirely replaced), if it is the start of a longer value, like `vis` for `visualization`, or
+/// if they start with the same four letters, like `maths` and `mathematics`.
+pub fn suggest(value: &str, list: &[&'static str]) -> Option<&'static str> {
+    let value = value.trim().to_lowercase();
+    if value.is_empty() {
+        return None;
+    }
+    let len = value.chars().count();
+    // Three letters are enough if they are the whole value.
+    let prefix = len.clamp(3, 4);
+    list.iter()
+        .map(|candidate| (distance(&value, candidate), *candidate))
+        .filter(|(distance, candidate)| {
+            let shared = value
+                .chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a == b);
+            (*distance <= 2 && *distance < len) || shared.count() >= prefix
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -166,6 +166,70 @@ exclude = [".git", "*.md", "tests/"]
         let content = read_file_string(&temp_dir.path().join("typst.toml"));
         assert!(content.contains("exclude = [\".git\", \"*.md\", \"tests/\"]"));
     }
+
+    #[test]
+    fn test_link_watch_flag() {
+        use clap::Parser;
+        use utpm::commands::{Cli, Commands, ProjectArgs};
+
+        let cli = Cli::try_parse_from(["utpm", "prj", "link", "--watch"]).unwrap();
+        let Commands::Project(ProjectArgs::Link(args)) = cli.command else {
+            panic!("Expected the link command");
+        };
+        assert!(args.watch);
+
+        // Symlinked packages are always up to date, there is nothing to watch.
+        assert!(Cli::try_parse_from(["utpm", "prj", "link", "--watch", "--no-copy"]).is_err());
+    }
+
+    #[test]
+    fn test_link_watch_sync() {
+        use clap::Parser;
+        use utpm::args::ARGS;
+        use utpm::commands::link::{snapshot, sync};
+        use utpm::commands::{Cli, Commands, ProjectArgs};
+        use utpm::utils::specs::Extra;
+
+        let cli = ARGS.get_or_init(|| Cli::try_parse_from(["utpm", "prj", "link"]).unwrap());
+        let Commands::Project(ProjectArgs::Link(cmd)) = &cli.command else {
+            panic!("Expected the link command");
+        };
+
+        let temp_dir = setup_temp_dir();
+        let project = temp_dir.path().join("project");
+        let destination = temp_dir.path().join("linked");
+        create_test_package(&project, "my-pkg", "1.0.0");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src/util.typ"), "#let util = 1").unwrap();
+        fs::create_dir_all(&destination).unwrap();
+
+        // The first pass copies the whole project.
+        let extra = Extra::default();
+        let known = snapshot(cmd, &project, &extra).unwrap();
+        let (changed, removed) = sync(&project, &destination, &Default::default(), &known).unwrap();
+        assert_eq!((changed, removed), (known.len(), 0));
+        assert_file_exists(&destination.join("src/util.typ"));
+
+        fs::write(project.join("main.typ"), "#let updated = true").unwrap();
+        fs::write(project.join("new.typ"), "new").unwrap();
+        fs::remove_dir_all(project.join("src")).unwrap();
+
+        let current = snapshot(cmd, &project, &extra).unwrap();
+        let (changed, removed) = sync(&project, &destination, &known, &current).unwrap();
+        assert_eq!((changed, removed), (2, 2));
+        assert_eq!(
+            read_file_string(&destination.join("main.typ")),
+            "#let updated = true"
+        );
+        assert_eq!(read_file_string(&destination.join("new.typ")), "new");
+        assert_not_exists(&destination.join("src"));
+
+        // Nothing is done once the copy is up to date.
+        assert_eq!(
+            sync(&project, &destination, &current, &current).unwrap(),
+            (0, 0)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -201,6 +265,44 @@ mod clone_command_tests {
 
         cleanup_test_env();
     }
+
+    #[test]
+    fn test_clone_redownload_with_download_only() {
+        use clap::Parser;
+        use utpm::commands::{Cli, Commands, ProjectArgs};
+
+        let cli =
+            Cli::try_parse_from(["utpm", "prj", "clone", "-r", "-d", "@preview/example:1.0.0"])
+                .unwrap();
+        let Commands::Project(ProjectArgs::Clone(args)) = cli.command else {
+            panic!("Expected the clone command");
+        };
+        assert!(args.redownload);
+        assert!(args.download_only);
+        assert_eq!(args.path, None);
+    }
+
+    #[test]
+    #[ignore = "needs network access to the preview registry"]
+    fn test_clone_redownload_keeps_cache_on_failure() {
+        use utpm::commands::clone::download;
+
+        let temp_dir = setup_temp_dir();
+        let destination = temp_dir.path().join("preview/utpm-missing-package/0.0.1");
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(destination.join("main.typ"), "// cached").unwrap();
+
+        // The package can't be downloaded, the cached copy must survive.
+        let spec = "@preview/utpm-missing-package:0.0.1".parse().unwrap();
+        assert!(download(&spec, &destination).is_err());
+
+        assert_eq!(read_file_string(&destination.join("main.typ")), "// cached");
+        let leftovers: Vec<_> = fs::read_dir(destination.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers, ["0.0.1"]);
+    }
 }
 
 #[cfg(test)]
@@ -281,6 +383,157 @@ mod bump_command_tests {
             assert!(PackageVersion::from_str(new).is_ok());
         }
     }
+
+    #[test]
+    fn test_bump_levels() {
+        use utpm::commands::bump::Bump;
+        use utpm::utils::state::UtpmError;
+
+        let current = PackageVersion::from_str("1.2.3").unwrap();
+        let bump = |s: &str| Bump::from_str(s).unwrap().apply(current).to_string();
+        assert_eq!(bump("major"), "2.0.0");
+        assert_eq!(bump("minor"), "1.3.0");
+        assert_eq!(bump("patch"), "1.2.4");
+        assert_eq!(bump("0.9.0"), "0.9.0");
+
+        assert!(matches!(
+            Bump::from_str("1.2"),
+            Err(UtpmError::InvalidVersion(input, _)) if input == "1.2"
+        ));
+        assert!(Bump::from_str("next").is_err());
+    }
+
+    #[test]
+    fn test_bump_replaces_only_package_versions() {
+        use std::path::Path;
+        use utpm::commands::bump::{Replacer, diff};
+
+        let readme = "Import with `#import \"@preview/mypkg:1.0.0\"`.\n\
+            Needs `@preview/other:1.0.0` and typst 1.0.0.\n";
+        let replacer = Replacer::new("mypkg", "1.0.0", "1.1.0", None, &[]).unwrap();
+        let bumped = replacer.replace(Path::new("README.md"), readme);
+        assert_eq!(
+            bumped,
+            "Import with `#import \"@preview/mypkg:1.1.0\"`.\n\
+            Needs `@preview/other:1.0.0` and typst 1.0.0.\n"
+        );
+        let changes = diff("README.md", readme, &bumped);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].line, 1);
+
+        // Other files get every version replaced, unless rules are given.
+        let json = "{\"version\": \"1.0.0\", \"typst\": \"11.0.0\"}";
+        assert_eq!(
+            replacer.replace(Path::new("package.json"), json),
+            "{\"version\": \"1.1.0\", \"typst\": \"11.0.0\"}"
+        );
+        let patterns = [r#""version": "{version}""#.to_string()];
+        let replacer = Replacer::new("mypkg", "1.0.0", "1.1.0", Some("v"), &patterns).unwrap();
+        let text = "<v>1.0.0</v> \"version\": \"1.0.0\" 1.0.0";
+        assert_eq!(
+            replacer.replace(Path::new("notes.txt"), text),
+            "<v>1.1.0</v> \"version\": \"1.1.0\" 1.0.0"
+        );
+
+        assert!(Replacer::new("mypkg", "1.0.0", "1.1.0", None, &["v(".into()]).is_err());
+        assert!(Replacer::new("mypkg", "1.0.0", "1.1.0", None, &["v".into()]).is_err());
+    }
+
+    #[test]
+    fn test_bump_changelog() {
+        use utpm::commands::bump::{changelog_entry, prepend_changelog};
+
+        let entry = changelog_entry("1.1.0", "2025-01-31", &["Add a thing".into()]);
+        assert_eq!(
+            entry,
+            "## [1.1.0] - 2025-01-31\n\n### Changed\n\n- Add a thing\n"
+        );
+        assert_eq!(
+            changelog_entry("1.0.1", "2025-02-01", &[]),
+            "## [1.0.1] - 2025-02-01\n"
+        );
+
+        // A new changelog gets the Keep a Changelog header.
+        let created = prepend_changelog(None, &entry);
+        assert!(created.starts_with("# Changelog\n"));
+        assert!(created.ends_with("\n\n## [1.1.0] - 2025-01-31\n\n### Changed\n\n- Add a thing\n"));
+
+        // Releases go below [Unreleased] and above the previous ones.
+        let existing = "# Changelog\n\n## [Unreleased]\n\n## [1.0.0] - 2024-12-01\n\n- Initial\n";
+        let updated = prepend_changelog(Some(existing), "## [1.1.0] - 2025-01-31\n");
+        assert_eq!(
+            updated,
+            "# Changelog\n\n## [Unreleased]\n\n## [1.1.0] - 2025-01-31\n\n## [1.0.0] - 2024-12-01\n\n- Initial\n"
+        );
+    }
+
+    #[test]
+    fn test_bump_git_flags() {
+        use clap::Parser;
+        use utpm::commands::{Cli, Commands, ProjectArgs};
+
+        let cli = Cli::try_parse_from([
+            "utpm",
+            "prj",
+            "bump",
+            "--commit",
+            "--tag-git",
+            "-m",
+            "Release {version}",
+            "patch",
+        ])
+        .unwrap();
+        let Commands::Project(ProjectArgs::Bump(args)) = cli.command else {
+            panic!("Expected the bump command");
+        };
+        assert!(args.commit && args.tag_git && !args.allow_dirty);
+        assert_eq!(args.message.as_deref(), Some("Release {version}"));
+        assert_eq!(args.new_version, "patch");
+
+        // Only the bump commit can be tagged.
+        assert!(Cli::try_parse_from(["utpm", "prj", "bump", "--tag-git", "patch"]).is_err());
+    }
+
+    #[test]
+    fn test_bump_finds_self_importing_files() {
+        use utpm::commands::bump::self_importing_files;
+        use utpm::utils::try_find;
+
+        let temp_dir = setup_temp_dir();
+        let root = temp_dir.path();
+        create_custom_manifest(
+            root,
+            r#"[package]
+name = "mypkg"
+version = "1.0.0"
+entrypoint = "lib.typ"
+authors = ["Test Author"]
+license = "MIT"
+description = "Test package"
+
+[template]
+path = "template"
+entrypoint = "main.typ"
+"#,
+        );
+        fs::create_dir_all(root.join("template")).unwrap();
+        fs::create_dir_all(root.join("examples/basic")).unwrap();
+        let import = "#import \"@preview/mypkg:1.0.0\": *";
+        fs::write(root.join("template/main.typ"), import).unwrap();
+        fs::write(root.join("examples/basic/demo.typ"), import).unwrap();
+        fs::write(
+            root.join("examples/other.typ"),
+            "#import \"@preview/mypkg:0.9.0\"",
+        )
+        .unwrap();
+        fs::write(root.join("lib.typ"), import).unwrap();
+
+        let manifest = try_find(root).unwrap();
+        assert_eq!(
+            self_importing_files(root, &manifest).unwrap(),
+            ["examples/basic/demo.typ", "template/main.typ"]
+        );
+    }
 }
 
 #[cfg(test)]
@@ -368,11 +621,317 @@ mod list_command_tests {
 
         cleanup_test_env();
     }
+
+    #[test]
+    fn test_list_long_flags_manifest_problems() {
+        use utpm::commands::list::{Store, package_read};
+
+        let temp_dir = setup_temp_dir();
+        let pkg_dir = temp_dir.path().join("local/mypkg");
+        create_test_manifest(&pkg_dir.join("1.0.0"), "mypkg", "1.0.0");
+        create_test_entrypoint(&pkg_dir.join("1.0.0"));
+        create_test_manifest(&pkg_dir.join("1.1.0"), "mypkg", "1.2.0");
+        fs::create_dir_all(pkg_dir.join("2.0.0")).unwrap();
+
+        let pkg = package_read(&pkg_dir, Store::Local, "local", "mypkg".into(), true)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(pkg.contains("mypkg:1.0.0, entrypoint main.typ"));
+        assert!(pkg.contains("Test package"));
+        assert!(pkg.contains("! manifest version is 1.2.0"));
+        assert!(pkg.contains("! missing entrypoint main.typ"));
+        assert!(pkg.contains("! missing manifest"));
+        assert!(!pkg.contains("1.0.0 [local, copy, invalid]"));
+    }
+
+    #[test]
+    fn test_list_deprecated_all_flag() {
+        use clap::Parser;
+        use utpm::commands::{Cli, Commands, PackagesArgs};
+
+        // `--all` is still accepted, though both stores are always listed now.
+        for flag in ["--all", "-a"] {
+            let cli = Cli::try_parse_from(["utpm", "pkg", "list", flag]).unwrap();
+            let Commands::Packages(PackagesArgs::List(args)) = cli.command else {
+                panic!("Expected the list command");
+            };
+            assert!(args.all);
+        }
+    }
+
+    #[test]
+    fn test_list_queries() {
+        use utpm::commands::list::Query;
+
+        let query: Query = "@preview/cet*".parse().unwrap();
+        assert!(query.matches("preview", "cetz"));
+        assert!(!query.matches("preview", "tablex"));
+        assert!(!query.matches("local", "cetz"));
+
+        let query: Query = "@local".parse().unwrap();
+        assert!(query.matches("local", "anything"));
+        assert!(!query.matches("preview", "anything"));
+
+        assert!("preview".parse::<Query>().is_err());
+        assert!("@preview/".parse::<Query>().is_err());
+        assert!("@preview/cetz:0.3.1".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn test_list_sort_and_latest_only() {
+        use utpm::commands::list::{SortKey, Store, latest_only, read, sort};
+
+        let temp_dir = setup_temp_dir();
+        for version in ["0.2.0", "0.10.0", "0.9.1"] {
+            create_test_manifest(
+                &temp_dir.path().join("preview/pkg").join(version),
+                "pkg",
+                version,
+            );
+        }
+        create_test_manifest(&temp_dir.path().join("local/other/1.0.0"), "other", "1.0.0");
+        let specs = |entries: &[utpm::commands::list::Entry]| {
+            entries
+                .iter()
+                .map(|e| e.to_string().split(' ').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut entries = read(temp_dir.path(), Store::Local, &[], false).unwrap();
+        sort(&mut entries, SortKey::Name);
+        assert_eq!(
+            specs(&entries),
+            [
+                "@local/other:1.0.0",
+                "@preview/pkg:0.2.0",
+                "@preview/pkg:0.9.1",
+                "@preview/pkg:0.10.0"
+            ]
+        );
+
+        sort(&mut entries, SortKey::Version);
+        assert_eq!(
+            specs(&entries)[..2],
+            ["@local/other:1.0.0", "@preview/pkg:0.10.0"]
+        );
+
+        let mut latest = latest_only(entries);
+        sort(&mut latest, SortKey::Name);
+        assert_eq!(
+            specs(&latest),
+            ["@local/other:1.0.0", "@preview/pkg:0.10.0"]
+        );
+
+        let queries = ["@preview/p*".parse().unwrap()];
+        let entries = read(temp_dir.path(), Store::Local, &queries, false).unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+}
+
+#[cfg(test)]
+mod prune_command_tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::time::Duration;
+    use utpm::commands::list::{Store, read};
+    use utpm::commands::prune::{parse_age, select, used_packages};
+
+    #[test]
+    fn test_prune_keeps_imported_and_latest() {
+        let temp_dir = setup_temp_dir();
+        for version in ["0.1.0", "0.2.0", "0.3.0", "0.10.0"] {
+            fs::create_dir_all(temp_dir.path().join("preview/pkg").join(version)).unwrap();
+        }
+        fs::create_dir_all(temp_dir.path().join("preview/other/1.0.0")).unwrap();
+        let entries = read(temp_dir.path(), Store::Cache, &[], false).unwrap();
+        let imports: HashSet<_> = ["@preview/pkg:0.2.0".parse().unwrap()].into();
+        let removed = |pruned: Vec<utpm::commands::prune::Pruned>| {
+            pruned.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+        };
+
+        let (pruned, kept) = select(entries.clone(), &imports, 0, None);
+        assert_eq!(kept, 1);
+        assert_eq!(
+            removed(pruned),
+            [
+                "- @preview/other:1.0.0 (0 B)",
+                "- @preview/pkg:0.10.0 (0 B)",
+                "- @preview/pkg:0.3.0 (0 B)",
+                "- @preview/pkg:0.1.0 (0 B)"
+            ]
+        );
+
+        let (pruned, kept) = select(entries.clone(), &imports, 1, None);
+        assert_eq!(kept, 3);
+        assert_eq!(
+            removed(pruned),
+            ["- @preview/pkg:0.3.0 (0 B)", "- @preview/pkg:0.1.0 (0 B)"]
+        );
+
+        // Everything has just been created, so nothing is older than the cutoff.
+        let (pruned, _) = select(entries, &imports, 0, Some(0));
+        assert!(pruned.is_empty());
+    }
+
+    #[test]
+    fn test_prune_keeps_dependencies() {
+        let temp_dir = setup_temp_dir();
+        let local = temp_dir.path().join("local");
+        let cache = temp_dir.path().join("cache");
+        fs::create_dir_all(local.join("local/utils/0.1.0")).unwrap();
+        fs::write(
+            local.join("local/utils/0.1.0/lib.typ"),
+            "#import \"@preview/tablex:0.0.8\"",
+        )
+        .unwrap();
+        fs::create_dir_all(cache.join("preview/cetz/0.3.0")).unwrap();
+        fs::write(
+            cache.join("preview/cetz/0.3.0/lib.typ"),
+            "#import \"@preview/oxifmt:0.2.1\"",
+        )
+        .unwrap();
+        for package in ["oxifmt/0.2.1", "tablex/0.0.8", "unused/1.0.0"] {
+            fs::create_dir_all(cache.join("preview").join(package)).unwrap();
+        }
+
+        let imports = ["@preview/cetz:0.3.0", "@local/utils:0.1.0"]
+            .iter()
+            .map(|spec| spec.parse().unwrap())
+            .collect();
+        let used = used_packages(imports, &local, &cache).unwrap();
+        let entries = read(&cache, Store::Cache, &[], false).unwrap();
+        let (pruned, kept) = select(entries, &used, 0, None);
+        assert_eq!(kept, 3);
+        let pruned: Vec<_> = pruned.iter().map(|p| p.to_string()).collect();
+        assert_eq!(pruned, ["- @preview/unused:1.0.0 (0 B)"]);
+    }
+
+    #[test]
+    fn test_prune_parse_age() {
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_age("7"), Ok(Duration::from_secs(7 * 86400)));
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3x").is_err());
+        assert_eq!(
+            parse_age("99999999999999999w"),
+            Err("`99999999999999999w` is too long".into())
+        );
+    }
+}
+
+#[cfg(test)]
+mod fetch_command_tests {
+    use super::*;
+    use std::collections::HashSet;
+    use utpm::commands::fetch::{Status, resolve};
+
+    #[test]
+    fn test_fetch_follows_imports() {
+        let temp_dir = setup_temp_dir();
+        let local = temp_dir.path().join("local");
+        let cache = temp_dir.path().join("cache");
+        fs::create_dir_all(local.join("local/utils/0.1.0")).unwrap();
+        fs::write(
+            local.join("local/utils/0.1.0/lib.typ"),
+            "#import \"@preview/cetz:0.3.1\"",
+        )
+        .unwrap();
+        fs::create_dir_all(cache.join("preview/tablex/0.0.8")).unwrap();
+
+        let imports: HashSet<_> = [
+            "@local/utils:0.1.0",
+            "@preview/tablex:0.0.8",
+            "@local/gone:1.0.0",
+            "@preview/broken:1.0.0",
+        ]
+        .iter()
+        .map(|spec| spec.parse().unwrap())
+        .collect();
+        let mut downloaded = vec![];
+        let packages = resolve(imports, &local, &cache, |spec, dir| {
+            downloaded.push(spec.to_string());
+            // A failed download doesn't stop the others.
+            if spec.name == "broken" {
+                return Ok(Status::Missing);
+            }
+            // The downloaded package imports another one.
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join("lib.typ"), "#import \"@preview/oxifmt:0.2.1\"").unwrap();
+            Ok(Status::Fetched)
+        })
+        .unwrap();
+
+        downloaded.sort();
+        assert_eq!(
+            downloaded,
+            [
+                "@preview/broken:1.0.0",
+                "@preview/cetz:0.3.1",
+                "@preview/oxifmt:0.2.1"
+            ]
+        );
+        let lines: Vec<_> = packages.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "missing @local/gone:1.0.0",
+                "local @local/utils:0.1.0",
+                "missing @preview/broken:1.0.0",
+                "fetched @preview/cetz:0.3.1",
+                "fetched @preview/oxifmt:0.2.1",
+                "cached @preview/tablex:0.0.8"
+            ]
+        );
+        assert!(
+            packages
+                .iter()
+                .all(|p| (p.status == Status::Missing) == p.path.is_none())
+        );
+    }
+}
+
+#[cfg(test)]
+mod du_command_tests {
+    use super::*;
+    use utpm::commands::du::Usage;
+
+    #[test]
+    fn test_du_sizes_by_level() {
+        let temp_dir = setup_temp_dir();
+        let store = temp_dir.path();
+        fs::create_dir_all(store.join("preview/big/1.0.0")).unwrap();
+        fs::create_dir_all(store.join("preview/small/0.1.0")).unwrap();
+        fs::write(store.join("preview/big/1.0.0/lib.typ"), vec![b'a'; 300]).unwrap();
+        fs::write(store.join("preview/small/0.1.0/lib.typ"), vec![b'a'; 20]).unwrap();
+
+        let usage = Usage::read(store, "local".into(), 3).unwrap();
+        assert_eq!(usage.size, 320);
+        let preview = &usage.children[0];
+        assert_eq!(preview.name, "preview");
+        let names: Vec<_> = preview.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["big", "small"]);
+        assert_eq!(preview.children[0].children[0].size, 300);
+
+        // Sizes don't depend on how deep the report goes.
+        let usage = Usage::read(store, "local".into(), 1).unwrap();
+        assert_eq!(usage.size, 320);
+        assert!(usage.children[0].children.is_empty());
+
+        let missing = Usage::read(&store.join("missing"), "cache".into(), 3).unwrap();
+        assert_eq!(missing.size, 0);
+    }
 }
 
 #[cfg(test)]
 mod metadata_command_tests {
     use super::*;
+    use utpm::utils::state::UtpmError;
 
     #[test]
     fn test_metadata_extracts_all_fields() {
@@ -401,6 +960,156 @@ mod metadata_command_tests {
         assert!(content.contains("name = \"my-package\""));
         assert!(content.contains("version = \"1.2.3\""));
     }
+
+    fn manifest() -> toml::Table {
+        toml::from_str(
+            r#"
+            [package]
+            name = "my-package"
+            version = "1.2.3"
+            authors = ["Ann", "Bob"]
+
+            [template]
+            path = "template"
+            entrypoint = "main.typ"
+
+            [tool.utpm]
+            exclude = [".git", "*.pdf"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_metadata_field_paths() {
+        use utpm::commands::metadata::{lookup, render};
+
+        let manifest = manifest();
+        let field = |path| lookup(&manifest, path).map(render);
+        assert_eq!(field("template.entrypoint").unwrap(), "main.typ");
+        assert_eq!(field("tool.utpm.exclude").unwrap(), ".git, *.pdf");
+        assert_eq!(field("package.authors[0]").unwrap(), "Ann");
+        // Short names still work.
+        assert_eq!(field("version").unwrap(), "1.2.3");
+        assert_eq!(field("authors").unwrap(), "Ann, Bob");
+        assert_eq!(field("exclude").unwrap(), ".git, *.pdf");
+
+        for path in [
+            "homepage",
+            "package.authors[2]",
+            "name.first",
+            "authors[x]",
+            "",
+        ] {
+            assert!(
+                matches!(field(path), Err(UtpmError::MetadataField(..))),
+                "{path} should fail"
+            );
+        }
+    }
+
+    #[test]
+    fn test_metadata_format() {
+        use utpm::commands::metadata::format_fields;
+
+        let manifest = manifest();
+        assert_eq!(
+            format_fields(&manifest, "{name}@{version} {{by}} {authors[1]}").unwrap(),
+            "my-package@1.2.3 {by} Bob"
+        );
+        assert!(format_fields(&manifest, "{license}").is_err());
+        assert!(format_fields(&manifest, "{name").is_err());
+        assert!(format_fields(&manifest, "name}").is_err());
+    }
+}
+
+#[cfg(test)]
+mod edit_command_tests {
+    use toml_edit::{DocumentMut, Value};
+    use utpm::commands::edit::{add_values, remove_values, set_value, validate};
+    use utpm::utils::state::UtpmError;
+
+    const MANIFEST: &str = r#"# Comments are kept
+[package]
+name = "my-package"
+version = "1.0.0"
+entrypoint = "lib.typ"
+description = "Old" # short
+keywords = ["a", "b"]
+"#;
+
+    fn doc() -> DocumentMut {
+        MANIFEST.parse().unwrap()
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_set_keeps_formatting() {
+        let mut doc = doc();
+        set_value(&mut doc, "description", Value::from("New")).unwrap();
+        set_value(
+            &mut doc,
+            "tool.utpm.exclude",
+            "[\"*.pdf\"]".parse().unwrap(),
+        )
+        .unwrap();
+        validate(&doc, "description").unwrap();
+
+        let out = doc.to_string();
+        assert!(out.starts_with("# Comments are kept\n"));
+        assert!(out.contains("description = \"New\" # short"));
+        assert!(out.contains("[tool.utpm]\nexclude = [\"*.pdf\"]"));
+    }
+
+    #[test]
+    fn test_set_is_validated() {
+        let mut doc = doc();
+        set_value(&mut doc, "package.version", Value::from("one")).unwrap();
+        assert!(matches!(
+            validate(&doc, "package.version"),
+            Err(UtpmError::ManifestEdit(..))
+        ));
+
+        assert!(set_value(&mut doc, "package", Value::from("x")).is_err());
+        assert!(set_value(&mut doc, "name.first", Value::from("x")).is_err());
+        assert!(set_value(&mut doc, "a..b", Value::from("x")).is_err());
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut doc = doc();
+        let added = add_values(&mut doc, "keywords", &strings(&["b", "c", "c"])).unwrap();
+        assert_eq!(added, vec!["c"]);
+        let removed = remove_values(&mut doc, "package.keywords", &strings(&["a", "z"])).unwrap();
+        assert_eq!(removed, vec!["a"]);
+        assert!(doc.to_string().contains("keywords = [\"b\", \"c\"]"));
+
+        add_values(&mut doc, "categories", &strings(&["layout"])).unwrap();
+        assert!(doc.to_string().contains("categories = [\"layout\"]"));
+        assert!(
+            remove_values(&mut doc, "disciplines", &strings(&["law"]))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(add_values(&mut doc, "name", &strings(&["x"])).is_err());
+    }
+
+    #[test]
+    fn test_lists_are_checked() {
+        let mut doc = doc();
+        assert!(matches!(
+            add_values(&mut doc, "categories", &strings(&["layouts"])),
+            Err(UtpmError::InvalidCategory(..))
+        ));
+        assert!(matches!(
+            set_value(&mut doc, "disciplines", "[\"maths\"]".parse().unwrap()),
+            Err(UtpmError::InvalidDiscipline(..))
+        ));
+        add_values(&mut doc, "disciplines", &strings(&["computer-science"])).unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -255,6 +255,151 @@ description = "Valid package"
     }
 }
 
+#[cfg(test)]
+mod install_update_tests {
+    use super::*;
+    use std::path::Path;
+    use std::process::Command;
+    use utpm::utils::git::{GitCli, GitRunner};
+    use utpm::utils::provenance::Provenance;
+    use utpm::utils::state::UtpmError;
+
+    /// Runs utpm with its stores inside `root`, returning whether it succeeded.
+    fn try_utpm(root: &Path, args: &[&str]) -> bool {
+        Command::new(env!("CARGO_BIN_EXE_utpm"))
+            .args(args)
+            .env("TYPST_PACKAGE_PATH", root.join("packages"))
+            .env("TYPST_PACKAGE_CACHE_PATH", root.join("cache"))
+            .env("UTPM_DATA_PATH", root.join("data"))
+            .output()
+            .unwrap()
+            .status
+            .success()
+    }
+
+    /// Runs utpm with its stores inside `root`.
+    fn utpm(root: &Path, args: &[&str]) {
+        assert!(try_utpm(root, args), "utpm {} failed", args.join(" "));
+    }
+
+    /// Creates an empty git repository for `name` in `root`, returning its URL.
+    ///
+    /// Returns `None` without git.
+    fn repository(root: &Path, name: &str) -> Option<String> {
+        let repo = root.join(name);
+        fs::create_dir_all(&repo).unwrap();
+        match GitCli.run(&repo, &["init", "--initial-branch=main"]) {
+            Err(UtpmError::GitNotFound) => return None,
+            other => other.unwrap(),
+        };
+        Some(format!("file://{}", repo.display()))
+    }
+
+    /// Commits a release of `version` of the package `name`, returning the commit.
+    fn release(root: &Path, name: &str, version: &str) -> String {
+        let repo = root.join(name);
+        create_test_package(&repo, name, version);
+        let git = |args: &[&str]| GitCli.run(&repo, args).unwrap();
+        git(&["add", "."]);
+        git(&[
+            "-c",
+            "user.name=Test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-m",
+            version,
+        ]);
+        git(&["rev-parse", "HEAD"]).trim().to_string()
+    }
+
+    /// Loads the provenance record of `@local/name`, if there is one.
+    fn provenance(root: &Path, name: &str) -> Option<Provenance> {
+        let record = root.join(format!("data/provenance/local/{name}.toml"));
+        record
+            .exists()
+            .then(|| toml::from_str(&read_file_string(&record)).unwrap())
+    }
+
+    #[test]
+    fn test_install_and_update_from_git() {
+        let temp_dir = setup_temp_dir();
+        let root = temp_dir.path();
+        // Nothing to test without git.
+        let Some(url) = repository(root, "my-pkg") else {
+            return;
+        };
+        let installed = root.join("packages/local/my-pkg");
+
+        let first = release(root, "my-pkg", "1.0.0");
+        utpm(root, &["pkg", "install", &url]);
+        assert_file_exists(&installed.join("1.0.0/main.typ"));
+        let record = provenance(root, "my-pkg").unwrap();
+        assert_eq!(record.source, url);
+        assert_eq!(record.commit, Some(first));
+        assert_eq!(record.versions, ["1.0.0"]);
+        // Installs aren't copies made by `prj link`.
+        let copies = root.join("data/linked-copies/local/my-pkg");
+        assert_not_exists(&copies.join("1.0.0.toml"));
+
+        // The new release is linked next to the installed one.
+        let second = release(root, "my-pkg", "1.1.0");
+        utpm(root, &["pkg", "update"]);
+        assert_file_exists(&installed.join("1.0.0/main.typ"));
+        assert_file_exists(&installed.join("1.1.0/main.typ"));
+        let record = provenance(root, "my-pkg").unwrap();
+        assert_eq!(record.commit, Some(second));
+        assert_eq!(record.versions, ["1.0.0", "1.1.0"]);
+        assert_not_exists(&copies.join("1.1.0.toml"));
+    }
+
+    #[test]
+    fn test_unlink_then_update() {
+        let temp_dir = setup_temp_dir();
+        let root = temp_dir.path();
+        let Some(url) = repository(root, "my-pkg") else {
+            return;
+        };
+        let installed = root.join("packages/local/my-pkg");
+        release(root, "my-pkg", "1.0.0");
+        utpm(root, &["pkg", "install", &url]);
+        release(root, "my-pkg", "1.1.0");
+        utpm(root, &["pkg", "update"]);
+
+        // An unlinked version is forgotten, not installed again.
+        utpm(root, &["pkg", "unlink", "-y", "@local/my-pkg:1.0.0"]);
+        assert_eq!(provenance(root, "my-pkg").unwrap().versions, ["1.1.0"]);
+        utpm(root, &["pkg", "update"]);
+        assert_not_exists(&installed.join("1.0.0"));
+
+        // So is a whole package.
+        utpm(root, &["pkg", "unlink", "-y", "@local/my-pkg"]);
+        assert!(provenance(root, "my-pkg").is_none());
+        utpm(root, &["pkg", "update"]);
+        assert_not_exists(&installed);
+    }
+
+    #[test]
+    fn test_update_continues_after_failure() {
+        let temp_dir = setup_temp_dir();
+        let root = temp_dir.path();
+        let (Some(gone), Some(kept)) = (repository(root, "gone"), repository(root, "kept")) else {
+            return;
+        };
+        release(root, "gone", "1.0.0");
+        release(root, "kept", "1.0.0");
+        utpm(root, &["pkg", "install", &gone]);
+        utpm(root, &["pkg", "install", &kept]);
+
+        // `gone` can't be fetched anymore, `kept` is still updated.
+        fs::remove_dir_all(root.join("gone")).unwrap();
+        release(root, "kept", "1.1.0");
+        assert!(!try_utpm(root, &["pkg", "update"]));
+        assert_file_exists(&root.join("packages/local/kept/1.1.0/main.typ"));
+        assert_eq!(provenance(root, "gone").unwrap().versions, ["1.0.0"]);
+    }
+}
+
 #[cfg(test)]
 mod error_handling_tests {
     use super::*;
@@ -88,6 +88,136 @@ mod sync_regex_tests {
     }
 }
 
+#[cfg(test)]
+mod package_ref_tests {
+    use typst_syntax::package::PackageVersion;
+    use utpm::utils::package_ref::{PackageRef, VersionReq};
+    use utpm::utils::state::UtpmError;
+
+    fn parse(s: &str) -> PackageRef {
+        s.parse().unwrap()
+    }
+
+    fn reason(s: &str) -> String {
+        match s.parse::<PackageRef>() {
+            Err(UtpmError::InvalidPackage(input, reason)) => {
+                assert_eq!(input, s);
+                reason
+            },
+            other => panic!("expected an invalid package for {s}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_package_ref_forms() {
+        let pkg = parse("@preview/my-pkg:0.10.2");
+        assert_eq!(pkg.namespace.as_deref(), Some("preview"));
+        assert_eq!(pkg.name.as_deref(), Some("my-pkg"));
+        assert_eq!(
+            pkg.spec("preview").unwrap().to_string(),
+            "@preview/my-pkg:0.10.2"
+        );
+
+        let pkg = parse("@local/my_pkg2");
+        assert_eq!(pkg.version, VersionReq::Any);
+        assert!(pkg.spec("preview").is_err());
+
+        let pkg = parse("@local");
+        assert_eq!(pkg.name, None);
+        assert!(pkg.require_name().is_err());
+
+        // Without namespace, each command picks its own default.
+        let pkg = parse("example:1.0.0");
+        assert_eq!(pkg.namespace, None);
+        assert_eq!(pkg.namespace_or("preview"), "preview");
+        assert_eq!(pkg.to_string(), "example:1.0.0");
+    }
+
+    #[test]
+    fn test_package_ref_ranges() {
+        let version = |s: &str| s.parse::<PackageVersion>().unwrap();
+        let pkg = parse("@preview/cetz:0.3");
+        assert!(matches!(pkg.version, VersionReq::Range(_)));
+        assert!(pkg.version.matches(&version("0.3.4")));
+        assert!(!pkg.version.matches(&version("0.4.0")));
+        assert_eq!(pkg.to_string(), "@preview/cetz:0.3");
+
+        let pkg = parse("cetz:1");
+        assert!(pkg.version.matches(&version("1.9.0")));
+        assert!(!pkg.version.matches(&version("2.0.0")));
+    }
+
+    #[test]
+    fn test_package_ref_errors() {
+        assert!(reason("@preview/1pkg:1.0.0").contains("not a valid package name"));
+        assert!(reason("@pre view/example").contains("namespace"));
+        assert!(reason("@").contains("namespace is missing"));
+        assert!(reason("@preview/example:").contains("version is empty"));
+        assert!(reason("@preview/example:1.0.x").contains("patch"));
+        assert!(reason("@preview:1.0.0").contains("needs a package name"));
+        assert!(reason("@preview/").contains("name"));
+
+        let err = "@preview/a b".parse::<PackageRef>().unwrap_err();
+        assert!(err.to_string().contains("Invalid package `@preview/a b`"));
+    }
+}
+
+#[cfg(test)]
+mod imports_tests {
+    use super::*;
+    use std::fs;
+    use utpm::utils::imports::{find_imports, scan_imports};
+
+    #[test]
+    fn test_find_imports() {
+        let content = r#"#import "@preview/cetz:0.3.1": canvas
+#import "@preview/my-pkg2:1.0.0"
+#include "@local/notes:0.1.0"
+// @preview/broken:1.0
+"#;
+        let specs = find_imports(content)
+            .iter()
+            .map(|spec| spec.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            specs,
+            [
+                "@preview/cetz:0.3.1",
+                "@preview/my-pkg2:1.0.0",
+                "@local/notes:0.1.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_imports() {
+        let temp_dir = setup_temp_dir();
+        fs::create_dir(temp_dir.path().join("chapters")).unwrap();
+        fs::write(
+            temp_dir.path().join("main.typ"),
+            "#import \"@preview/cetz:0.3.1\"",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("chapters/intro.typ"),
+            "#import \"@preview/tablex:0.0.8\"\n#import \"@preview/cetz:0.3.1\"",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("README.md"),
+            "#import \"@preview/ignored:1.0.0\"",
+        )
+        .unwrap();
+
+        let specs = scan_imports(temp_dir.path()).unwrap();
+        assert_eq!(specs.len(), 2);
+
+        // A single file is read whatever its extension, e.g. a lockfile.
+        let specs = scan_imports(&temp_dir.path().join("README.md")).unwrap();
+        assert_eq!(specs.len(), 1);
+    }
+}
+
 #[cfg(test)]
 mod paths_tests {
     use super::*;
@@ -151,7 +281,8 @@ mod paths_tests {
 #[cfg(test)]
 mod specs_tests {
     use ecow::eco_vec;
-    use utpm::utils::specs::Extra;
+    use utpm::utils::specs::{BumpConfig, Extra};
+    use utpm::utils::state::UtpmError;
 
     #[test]
     fn test_extra_default() {
@@ -163,6 +294,7 @@ mod specs_tests {
     fn test_extra_serialization() {
         let extra = Extra {
             exclude: Some(eco_vec![String::from("*.md"), String::from(".git")]),
+            ..Default::default()
         };
 
         // Test that it can be serialized
@@ -179,6 +311,7 @@ mod specs_tests {
                 String::from("*.md"),
                 String::from("tests/"),
             ]),
+            ..Default::default()
         };
 
         assert!(extra.exclude.is_some());
@@ -188,6 +321,43 @@ mod specs_tests {
         assert!(excludes.iter().any(|s| s == "*.md"));
     }
 
+    #[test]
+    fn test_extra_bump_section() {
+        let manifest: typst_syntax::package::PackageManifest = toml::from_str(
+            r#"[package]
+name = "mypkg"
+version = "1.0.0"
+entrypoint = "lib.typ"
+
+[tool.utpm.bump]
+files = ["README.md"]
+tag = "version"
+"#,
+        )
+        .unwrap();
+        let bump = BumpConfig::from_tool(&manifest.tool).unwrap().unwrap();
+        assert_eq!(bump.files, ["README.md"]);
+        assert_eq!(bump.tag.as_deref(), Some("version"));
+        assert!(bump.patterns.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_bump_section() {
+        let manifest: typst_syntax::package::PackageManifest = toml::from_str(
+            r#"[package]
+name = "mypkg"
+version = "1.0.0"
+entrypoint = "lib.typ"
+
+[tool.utpm.bump]
+files = "README.md"
+"#,
+        )
+        .unwrap();
+        let err = BumpConfig::from_tool(&manifest.tool).unwrap_err();
+        assert!(matches!(err, UtpmError::BumpConfig(_)));
+    }
+
     #[test]
     fn test_extra_new() {
         let excludes = eco_vec![String::from(".git"), String::from("*.log")];
@@ -201,6 +371,543 @@ mod specs_tests {
     }
 }
 
+#[cfg(test)]
+mod format_tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+    use utpm::utils::{dir_stats, format_date, format_size};
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(UNIX_EPOCH), "1970-01-01");
+        assert_eq!(
+            format_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29"
+        );
+        assert_eq!(
+            format_date(UNIX_EPOCH + Duration::from_secs(1_735_689_599)),
+            "2024-12-31"
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        use utpm::utils::format_duration;
+
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 05s");
+        assert_eq!(format_duration(Duration::from_secs(7320)), "2h 02m");
+    }
+
+    #[test]
+    fn test_download_progress() {
+        use std::collections::VecDeque;
+        use std::time::Instant;
+        use typst_kit::download::DownloadState;
+        use utpm::utils::DownloadProgress;
+
+        let state = DownloadState {
+            content_len: Some(4096),
+            total_downloaded: 1024,
+            bytes_per_second: VecDeque::from([512, 1536]),
+            start_time: Instant::now(),
+        };
+        let progress = DownloadProgress::new("@preview/cetz:0.3.1", &state, false);
+        assert_eq!(progress.speed, 1024);
+        assert_eq!(progress.eta, Some(3));
+        assert_eq!(
+            progress.describe(),
+            "@preview/cetz:0.3.1 1.0 KiB / 4.0 KiB, 1.0 KiB/s, 3s left"
+        );
+
+        // Without a known size, there is no time left to estimate.
+        let state = DownloadState {
+            content_len: None,
+            ..state
+        };
+        let progress = DownloadProgress::new("@preview/cetz:0.3.1", &state, true);
+        assert_eq!(progress.eta, None);
+        assert_eq!(progress.describe(), "@preview/cetz:0.3.1 1.0 KiB in 0s");
+    }
+
+    #[test]
+    fn test_dir_stats() {
+        let temp_dir = setup_temp_dir();
+        fs::write(temp_dir.path().join("a.typ"), "12345").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub/b.typ"), "123").unwrap();
+
+        let (size, modified) = dir_stats(temp_dir.path()).unwrap();
+        assert_eq!(size, 8);
+        assert!(modified.is_some());
+    }
+}
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::*;
+    use std::fs;
+    use utpm::utils::checksum::hash_dir;
+
+    fn package(dir: &std::path::Path) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("lib.typ"), "#import \"src/a.typ\"").unwrap();
+        fs::write(dir.join("src/a.typ"), "#let a = 1").unwrap();
+    }
+
+    #[test]
+    fn test_hash_dir_is_stable() {
+        let temp_dir = setup_temp_dir();
+        package(&temp_dir.path().join("one"));
+        package(&temp_dir.path().join("two"));
+
+        let one = hash_dir(&temp_dir.path().join("one")).unwrap();
+        assert_eq!(one.len(), 64);
+        assert_eq!(one, hash_dir(&temp_dir.path().join("two")).unwrap());
+    }
+
+    #[test]
+    fn test_hash_dir_detects_changes() {
+        let temp_dir = setup_temp_dir();
+        package(temp_dir.path());
+        let before = hash_dir(temp_dir.path()).unwrap();
+
+        fs::write(temp_dir.path().join("src/a.typ"), "#let a = 2").unwrap();
+        let edited = hash_dir(temp_dir.path()).unwrap();
+        assert_ne!(before, edited);
+
+        fs::rename(
+            temp_dir.path().join("src/a.typ"),
+            temp_dir.path().join("src/b.typ"),
+        )
+        .unwrap();
+        assert_ne!(edited, hash_dir(temp_dir.path()).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod provenance_tests {
+    use utpm::utils::provenance::{DevLink, Provenance};
+
+    #[test]
+    fn test_add_version_sorted_and_unique() {
+        let mut record = Provenance::new("local", "pkg", "https://example.com/pkg.git", None, None);
+        record.add_version("1.10.0");
+        record.add_version("1.9.0");
+        record.add_version("1.10.0");
+
+        assert_eq!(record.versions, vec!["1.9.0", "1.10.0"]);
+    }
+
+    #[test]
+    fn test_provenance_roundtrip() {
+        let mut record = Provenance::new(
+            "local",
+            "pkg",
+            "https://example.com/pkg.git",
+            Some(String::from("v1.0.0")),
+            Some(String::from("abc123")),
+        );
+        record.add_version("1.0.0");
+
+        let serialized = toml::to_string(&record).unwrap();
+        assert!(serialized.contains("ref = \"v1.0.0\""));
+
+        let parsed: Provenance = toml::from_str(&serialized).unwrap();
+        assert_eq!(parsed, record);
+    }
+
+    #[test]
+    fn test_dev_link_without_alias() {
+        let link = DevLink {
+            namespace: String::from("local"),
+            name: String::from("pkg"),
+            version: String::from("0.0.1"),
+            manifest_version: String::from("1.0.0"),
+            alias: None,
+            source: String::from("/home/user/pkg"),
+        };
+
+        let serialized = toml::to_string(&link).unwrap();
+        assert!(!serialized.contains("alias"));
+
+        let parsed: DevLink = toml::from_str(&serialized).unwrap();
+        assert_eq!(parsed, link);
+    }
+}
+
+#[cfg(test)]
+mod store_tests {
+    use super::*;
+    use std::fs;
+    use utpm::utils::store::{commit_staged, replace, stage_dir, write_files};
+
+    #[test]
+    fn test_commit_staged_moves_directory() {
+        let temp_dir = setup_temp_dir();
+        let destination = temp_dir.path().join("local/pkg/1.0.0");
+
+        let staged = stage_dir(&destination).unwrap();
+        let staged_path = staged.path().to_path_buf();
+        fs::write(staged_path.join("main.typ"), "// staged").unwrap();
+
+        commit_staged(staged, &destination).unwrap();
+
+        assert_file_exists(&destination.join("main.typ"));
+        assert_not_exists(&staged_path);
+    }
+
+    #[test]
+    fn test_replace_swaps_existing_version() {
+        let temp_dir = setup_temp_dir();
+        let destination = temp_dir.path().join("local/pkg/1.0.0");
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(destination.join("old.typ"), "// old").unwrap();
+
+        let staged = stage_dir(&destination).unwrap();
+        fs::write(staged.path().join("new.typ"), "// new").unwrap();
+        replace(staged.path(), &destination).unwrap();
+
+        assert_file_exists(&destination.join("new.typ"));
+        assert_not_exists(&destination.join("old.typ"));
+        // Nothing but the new version is left in the package directory.
+        let entries = fs::read_dir(temp_dir.path().join("local/pkg")).unwrap();
+        assert_eq!(entries.count(), 1);
+    }
+
+    #[test]
+    fn test_write_files_all_or_nothing() {
+        let temp_dir = setup_temp_dir();
+        let readme = temp_dir.path().join("README.md");
+        let manifest = temp_dir.path().join("typst.toml");
+        fs::write(&readme, "1.0.0").unwrap();
+        fs::write(&manifest, "1.0.0").unwrap();
+
+        // The changelog can't be written, so no file is.
+        let missing = temp_dir.path().join("missing/CHANGELOG.md");
+        let files = [
+            (readme.clone(), "1.1.0".to_string()),
+            (missing, "1.1.0".to_string()),
+            (manifest.clone(), "1.1.0".to_string()),
+        ];
+        assert!(write_files(&files).is_err());
+        assert_eq!(read_file_string(&readme), "1.0.0");
+        assert_eq!(read_file_string(&manifest), "1.0.0");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+
+        write_files(&[files[0].clone(), files[2].clone()]).unwrap();
+        assert_eq!(read_file_string(&readme), "1.1.0");
+        assert_eq!(read_file_string(&manifest), "1.1.0");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_replace_keeps_old_version_on_failure() {
+        let temp_dir = setup_temp_dir();
+        let destination = temp_dir.path().join("local/pkg/1.0.0");
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(destination.join("old.typ"), "// old").unwrap();
+
+        let missing = temp_dir.path().join("local/pkg/missing");
+        assert!(replace(&missing, &destination).is_err());
+
+        assert_file_exists(&destination.join("old.typ"));
+    }
+
+    #[test]
+    fn test_dropped_stage_is_removed() {
+        let temp_dir = setup_temp_dir();
+        let destination = temp_dir.path().join("local/pkg/1.0.0");
+
+        let staged = stage_dir(&destination).unwrap();
+        let staged_path = staged.path().to_path_buf();
+        drop(staged);
+
+        assert_not_exists(&staged_path);
+        assert_not_exists(&destination);
+    }
+}
+
+#[cfg(test)]
+mod git_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use utpm::utils::git::{Git, GitCli, GitOps, GitRunner};
+    use utpm::utils::state::{Result, UtpmError};
+
+    /// Records the commands it is given and answers with a canned output.
+    struct FakeGit {
+        calls: RefCell<Vec<(PathBuf, Vec<String>)>>,
+        output: Result<String>,
+    }
+
+    impl FakeGit {
+        fn new(output: Result<String>) -> Self {
+            Self {
+                calls: RefCell::new(vec![]),
+                output,
+            }
+        }
+    }
+
+    impl GitRunner for FakeGit {
+        fn run(&self, dir: &Path, args: &[&str]) -> Result<String> {
+            self.calls.borrow_mut().push((
+                dir.to_path_buf(),
+                args.iter().map(|arg| arg.to_string()).collect(),
+            ));
+            match &self.output {
+                Ok(out) => Ok(out.clone()),
+                Err(err) => Err(UtpmError::Git(err.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_commands_run_in_dir() {
+        let git = Git::with_runner(FakeGit::new(Ok(String::new())), "/tmp/project");
+        git.commit("Release 1.0.0").unwrap();
+        git.tag("v1.0.0").unwrap();
+
+        let calls = git_calls(&git);
+        assert_eq!(
+            calls,
+            vec![
+                (
+                    PathBuf::from("/tmp/project"),
+                    vec!["commit".to_string(), "-m".into(), "Release 1.0.0".into()]
+                ),
+                (
+                    PathBuf::from("/tmp/project"),
+                    vec!["tag".to_string(), "v1.0.0".into()]
+                ),
+            ]
+        );
+    }
+
+    fn git_calls(git: &Git<FakeGit>) -> Vec<(PathBuf, Vec<String>)> {
+        git.runner().calls.borrow().clone()
+    }
+
+    #[test]
+    fn test_output_is_parsed() {
+        let git = Git::with_runner(FakeGit::new(Ok("abc123\n".into())), ".");
+        assert_eq!(git.head_commit().unwrap(), "abc123");
+
+        let git = Git::with_runner(FakeGit::new(Ok(" M typst.toml\n".into())), ".");
+        assert!(git.is_dirty().unwrap());
+
+        let git = Git::with_runner(FakeGit::new(Ok("Fix\nAdd\n".into())), ".");
+        assert_eq!(
+            git.log_subjects(Some("v1.0.0")).unwrap(),
+            vec!["Fix", "Add"]
+        );
+        assert_eq!(
+            git_calls(&git)[0].1,
+            vec!["log", "--no-merges", "--format=%s", "v1.0.0..HEAD"]
+        );
+    }
+
+    #[test]
+    fn test_failures_propagate() {
+        let failing = || FakeGit::new(Err(UtpmError::Git("fatal: no remote".into())));
+
+        let err = Git::with_runner(failing(), ".")
+            .push("origin", "main")
+            .unwrap_err();
+        assert!(matches!(err, UtpmError::Git(ref msg) if msg.contains("no remote")));
+
+        // Having no tag isn't an error.
+        assert_eq!(Git::with_runner(failing(), ".").last_tag().unwrap(), None);
+    }
+
+    #[test]
+    fn test_cli_reports_failures() {
+        let dir = setup_temp_dir();
+        match GitCli.run(dir.path(), &["rev-parse", "HEAD"]) {
+            Err(UtpmError::Git(msg)) => {
+                assert!(msg.contains("git rev-parse HEAD"));
+                assert!(msg.contains("not a git repository"));
+            },
+            // Nothing to test without git.
+            Err(UtpmError::GitNotFound) => {},
+            other => panic!("expected a git error, got {other:?}"),
+        }
+    }
+
+    /// Lets `work` commit without a global git identity.
+    fn set_identity(work: &Path) {
+        let config = work.join(".git/config");
+        let mut content = fs::read_to_string(&config).unwrap();
+        content.push_str("[user]\n\tname = Test\n\temail = test@example.com\n");
+        fs::write(config, content).unwrap();
+    }
+
+    /// Exercises a backend against `root/remote.git`, a bare repository with a
+    /// `main` branch, and `root/work`, an empty repository using it as `origin`.
+    fn round_trip(root: &Path, open: impl Fn(&Path) -> Box<dyn GitOps>) {
+        let remote = root.join("remote.git");
+        let work = root.join("work");
+        set_identity(&work);
+        fs::create_dir_all(work.join("pkg")).unwrap();
+        fs::write(work.join("pkg/lib.typ"), "= First").unwrap();
+        fs::write(work.join("README.md"), "readme").unwrap();
+
+        // Paths are relative to the directory the backend runs in.
+        let pkg = open(&work.join("pkg"));
+        pkg.add(".").unwrap();
+        pkg.commit("First").unwrap();
+        let git = open(&work);
+        assert!(!git.is_dirty().unwrap());
+        git.add("README.md").unwrap();
+        assert!(git.is_dirty().unwrap());
+        git.commit("Second").unwrap();
+        assert!(matches!(git.commit("Nothing"), Err(UtpmError::Git(_))));
+        assert_eq!(git.last_tag().unwrap(), None);
+        git.tag("v1.0.0").unwrap();
+        assert_eq!(git.last_tag().unwrap().as_deref(), Some("v1.0.0"));
+        git.push("origin", "main").unwrap();
+
+        open(root)
+            .clone(&remote.to_string_lossy(), Path::new("copy"))
+            .unwrap();
+        let copy = open(&root.join("copy"));
+        let tagged = git.head_commit().unwrap();
+        assert_eq!(copy.head_commit().unwrap(), tagged);
+
+        fs::write(work.join("pkg/lib.typ"), "= Third").unwrap();
+        git.add("pkg").unwrap();
+        git.commit("Third").unwrap();
+        git.push("origin", "main").unwrap();
+        assert_eq!(git.log_subjects(Some("v1.0.0")).unwrap(), vec!["Third"]);
+        assert_eq!(
+            git.log_subjects(None).unwrap(),
+            vec!["Third", "Second", "First"]
+        );
+
+        copy.pull("origin", "main").unwrap();
+        assert_eq!(copy.head_commit().unwrap(), git.head_commit().unwrap());
+        let lib = root.join("copy/pkg/lib.typ");
+        assert_eq!(fs::read_to_string(&lib).unwrap(), "= Third");
+        copy.checkout(&tagged).unwrap();
+        assert_eq!(copy.head_commit().unwrap(), tagged);
+        assert_eq!(fs::read_to_string(&lib).unwrap(), "= First");
+
+        assert!(matches!(
+            copy.push("nowhere", "main"),
+            Err(UtpmError::Git(_))
+        ));
+    }
+
+    #[test]
+    fn test_cli_round_trip() {
+        let root = setup_temp_dir();
+        let root = root.path();
+        let init = |args: &[&str]| GitCli.run(root, args);
+        match init(&["init", "--bare", "--initial-branch=main", "remote.git"]) {
+            // Nothing to test without git.
+            Err(UtpmError::GitNotFound) => return,
+            other => other.unwrap(),
+        };
+        init(&["init", "--initial-branch=main", "work"]).unwrap();
+        init(&["-C", "work", "remote", "add", "origin", "../remote.git"]).unwrap();
+
+        round_trip(root, |dir| Box::new(Git::new(dir)));
+    }
+
+    #[cfg(feature = "git_lib")]
+    #[test]
+    fn test_lib_round_trip() {
+        use git2::{Repository, RepositoryInitOptions};
+        use utpm::utils::git::GitLib;
+
+        let root = setup_temp_dir();
+        let root = root.path();
+        let remote = root.join("remote.git");
+        Repository::init_opts(
+            &remote,
+            RepositoryInitOptions::new().bare(true).initial_head("main"),
+        )
+        .unwrap();
+        let work = Repository::init_opts(
+            root.join("work"),
+            RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        work.remote("origin", &remote.to_string_lossy()).unwrap();
+
+        round_trip(root, |dir| Box::new(GitLib::new(dir)));
+    }
+}
+
+#[cfg(test)]
+mod universe_tests {
+    use typst_syntax::package::PackageManifest;
+    use utpm::utils::state::UtpmError;
+    use utpm::utils::universe::{
+        CATEGORIES, DISCIPLINES, check_category, check_discipline, lint, suggest,
+    };
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(suggest("layouts", CATEGORIES), Some("layout"));
+        assert_eq!(suggest("Layout", CATEGORIES), Some("layout"));
+        assert_eq!(suggest("vizualization", CATEGORIES), Some("visualization"));
+        assert_eq!(suggest("vis", CATEGORIES), Some("visualization"));
+        assert_eq!(
+            suggest("computer science", DISCIPLINES),
+            Some("computer-science")
+        );
+        assert_eq!(suggest("maths", DISCIPLINES), Some("mathematics"));
+        assert_eq!(suggest("xyz", CATEGORIES), None);
+        assert_eq!(suggest("ab", CATEGORIES), None);
+        assert_eq!(suggest("", CATEGORIES), None);
+    }
+
+    #[test]
+    fn test_checks() {
+        check_category("utility").unwrap();
+        check_discipline("physics").unwrap();
+
+        let err = check_category("fnu").unwrap_err();
+        assert!(matches!(err, UtpmError::InvalidCategory(..)));
+        assert!(err.to_string().contains("Did you mean `fun`?"));
+        // Without a close match, every valid value is listed.
+        let err = check_discipline("cooking").unwrap_err();
+        assert!(err.to_string().contains("transportation"));
+    }
+
+    #[test]
+    fn test_lint() {
+        let manifest: PackageManifest = toml::from_str(
+            r#"
+            [package]
+            name = "pkg"
+            version = "1.0.0"
+            entrypoint = "lib.typ"
+            categories = ["layout", "slides"]
+            disciplines = ["law", "maths"]
+            "#,
+        )
+        .unwrap();
+        let problems = lint(&manifest.package);
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(problems[0], UtpmError::InvalidCategory(ref c, _) if c == "slides"));
+        assert!(matches!(problems[1], UtpmError::InvalidDiscipline(ref d, _) if d == "maths"));
+    }
+}
+
 #[cfg(test)]
 mod state_tests {
     use utpm::utils::state::UtpmError;
@@ -247,6 +954,10 @@ mod state_tests {
             UtpmError::PackageNotExist,
             UtpmError::ContentFound,
             UtpmError::PackageFormatError,
+            UtpmError::InvalidPackage("@preview".into(), "a package name is required".into()),
+            UtpmError::NoImports("~/docs".into()),
+            UtpmError::MissingPackages("@preview/cetz:0.3.1".into()),
+            UtpmError::UpdateFailed("@local/mypkg".into()),
         ];
 
         for err in errors {